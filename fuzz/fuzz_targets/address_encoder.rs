@@ -0,0 +1,39 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate ed25519_dalek;
+extern crate hex;
+extern crate num_bigint;
+extern crate sha2;
+
+// The main crate is a binary, so the modules under test are included directly
+#[path = "../../src/cpu/mod.rs"]
+#[allow(dead_code)]
+mod cpu;
+#[path = "../../src/derivation.rs"]
+#[allow(dead_code)]
+mod derivation;
+
+use std::convert::TryFrom;
+
+use derivation::pubkey_to_address;
+
+fuzz_target!(|data: &[u8]| {
+    let pubkey = match <&[u8; 32]>::try_from(data) {
+        Ok(pubkey) => pubkey,
+        Err(_) => return,
+    };
+    let address = pubkey_to_address(pubkey);
+
+    // Must render as 1 to 20 decimal digits plus the "L" suffix and parse back losslessly
+    let rendered = format!("{}L", address);
+    let digits = &rendered[..rendered.len() - 1];
+    assert!(rendered.ends_with('L'));
+    assert!(!digits.is_empty() && digits.len() <= 20);
+    assert!(digits.bytes().all(|byte| byte.is_ascii_digit()));
+    assert!(digits == "0" || !digits.starts_with('0'));
+    assert_eq!(digits.parse::<u64>().unwrap(), address);
+
+    // Deterministic for the same public key
+    assert_eq!(pubkey_to_address(pubkey), address);
+});
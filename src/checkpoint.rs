@@ -0,0 +1,230 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use error::Error;
+
+// Number of keys a CPU worker tries between publishing its position
+pub const PUBLISH_INTERVAL: u64 = 256;
+
+// Reads the worker positions from a checkpoint file, one hex encoded key per line.
+// A missing file is an empty checkpoint.
+pub fn load_checkpoint(path: &Path) -> Result<Vec<[u8; 32]>, Error> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(Error::Io(error)),
+    };
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut key = [0u8; 32];
+            hex::decode_to_slice(line.trim(), &mut key).map_err(|_| {
+                Error::Argument(format!(
+                    "Invalid key \"{}\" in checkpoint {}",
+                    line.trim(),
+                    path.display()
+                ))
+            })?;
+            Ok(key)
+        })
+        .collect()
+}
+
+// Replaces the checkpoint file, such that a crash never leaves a partial checkpoint behind
+pub fn save_checkpoint(path: &Path, keys: &[[u8; 32]]) -> Result<(), Error> {
+    let mut content = keys
+        .iter()
+        .map(hex::encode_upper)
+        .collect::<Vec<_>>()
+        .join("\n");
+    if !content.is_empty() {
+        content.push('\n');
+    }
+    replace_file(path, &content)
+}
+
+fn replace_file(path: &Path, content: &str) -> Result<(), Error> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // Checkpoints hold private keys
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&temp_path)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+// How often the state file is updated during a search
+pub const STATE_SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+// Totals of a search over all sessions (see --state-file)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchState {
+    // Description of the pattern. The totals of another pattern are not continued.
+    pub pattern: String,
+    pub attempts: u64,
+    pub elapsed: Duration,
+}
+
+impl SearchState {
+    fn encode(&self) -> String {
+        format!(
+            "pattern={}\nattempts={}\nseconds={}\n",
+            self.pattern,
+            self.attempts,
+            self.elapsed.as_secs()
+        )
+    }
+
+    fn decode(content: &str) -> Option<SearchState> {
+        let mut lines = content.lines();
+        let mut field = |name: &str| {
+            lines
+                .next()
+                .and_then(|line| line.strip_prefix(name))
+                .and_then(|line| line.strip_prefix('='))
+                .map(|value| value.to_string())
+        };
+        Some(SearchState {
+            pattern: field("pattern")?,
+            attempts: field("attempts")?.parse().ok()?,
+            elapsed: Duration::from_secs(field("seconds")?.parse().ok()?),
+        })
+    }
+}
+
+// The state file of a search, with the totals of the sessions before the current one
+pub struct StateFile {
+    path: PathBuf,
+    pub previous: SearchState,
+}
+
+impl StateFile {
+    // A missing or invalid file, or one of another pattern, starts from zero
+    pub fn open(path: PathBuf, pattern: &str) -> StateFile {
+        let fresh = SearchState {
+            pattern: pattern.to_string(),
+            attempts: 0,
+            elapsed: Duration::from_secs(0),
+        };
+        let previous = match fs::read_to_string(&path) {
+            Ok(content) => match SearchState::decode(&content) {
+                Some(ref state) if state.pattern != pattern => {
+                    eprintln!(
+                        "State file {} is for \"{}\", starting from zero",
+                        path.display(),
+                        state.pattern
+                    );
+                    fresh
+                }
+                Some(state) => state,
+                None => {
+                    eprintln!("Invalid state file {}, starting from zero", path.display());
+                    fresh
+                }
+            },
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => fresh,
+            Err(error) => {
+                eprintln!(
+                    "Failed to read state file {}: {}, starting from zero",
+                    path.display(),
+                    error
+                );
+                fresh
+            }
+        };
+        StateFile { path, previous }
+    }
+
+    // The totals including the current session
+    pub fn total(&self, attempts: u64, elapsed: Duration) -> SearchState {
+        SearchState {
+            pattern: self.previous.pattern.clone(),
+            attempts: self.previous.attempts + attempts,
+            elapsed: self.previous.elapsed + elapsed,
+        }
+    }
+
+    pub fn save(&self, attempts: u64, elapsed: Duration) -> Result<(), Error> {
+        replace_file(&self.path, &self.total(attempts, elapsed).encode())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // importing names from outer (for mod tests) scope.
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let dir = env::temp_dir().join(format!("lisk-vanity-checkpoint-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("checkpoint");
+
+        assert_eq!(load_checkpoint(&path).unwrap(), Vec::<[u8; 32]>::new());
+        let keys = [[1u8; 32], [0xabu8; 32]];
+        save_checkpoint(&path, &keys).unwrap();
+        assert_eq!(load_checkpoint(&path).unwrap(), keys.to_vec());
+        save_checkpoint(&path, &keys[..1]).unwrap();
+        assert_eq!(load_checkpoint(&path).unwrap(), keys[..1].to_vec());
+
+        fs::write(&path, "0101\n").unwrap();
+        assert!(load_checkpoint(&path).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_state_file() {
+        let dir = env::temp_dir().join(format!("lisk-vanity-state-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state");
+
+        let state = StateFile::open(path.clone(), "max length 14");
+        assert_eq!(state.previous.attempts, 0);
+        state.save(1000, Duration::from_secs(60)).unwrap();
+        let state = StateFile::open(path.clone(), "max length 14");
+        assert_eq!(state.previous.attempts, 1000);
+        state.save(500, Duration::from_secs(30)).unwrap();
+        let state = StateFile::open(path.clone(), "max length 14");
+        assert_eq!(
+            state.total(1, Duration::from_secs(1)),
+            SearchState {
+                pattern: "max length 14".to_string(),
+                attempts: 1501,
+                elapsed: Duration::from_secs(91),
+            }
+        );
+
+        // another pattern or a corrupt file start from zero
+        assert_eq!(
+            StateFile::open(path.clone(), "max length 13")
+                .previous
+                .attempts,
+            0
+        );
+        fs::write(&path, "pattern=max length 14\nattempts=lots\n").unwrap();
+        assert_eq!(
+            StateFile::open(path.clone(), "max length 14")
+                .previous
+                .attempts,
+            0
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -5,7 +5,7 @@
 use sha2::{Digest, Sha256};
 
 // https://github.com/bitcoin/bips/blob/master/bip-0039/english.txt
-const WORDS: [&[u8]; 2048] = [
+pub const WORDS: [&[u8]; 2048] = [
     b"abandon",
     b"ability",
     b"able",
@@ -1,7 +1,8 @@
 use std::convert::TryFrom;
 
-use cpu::bip39::entropy_to_mnemonic;
+use cpu::bip39::{entropy_to_mnemonic, WORDS};
 use ed25519_dalek::{PublicKey, SecretKey};
+use num_bigint::{BigInt, Sign};
 use sha2::{Digest, Sha256, Sha512};
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
@@ -11,6 +12,72 @@ pub enum GenerateKeyType {
     PrivateKey,
 }
 
+pub const KEY_TYPES: [GenerateKeyType; 2] =
+    [GenerateKeyType::LiskPassphrase, GenerateKeyType::PrivateKey];
+
+impl GenerateKeyType {
+    // Name in machine readable output (see --capabilities)
+    pub fn name(self) -> &'static str {
+        match self {
+            GenerateKeyType::LiskPassphrase => "passphrase",
+            GenerateKeyType::PrivateKey => "keypair",
+        }
+    }
+
+    // Identifies the key type in the GPU kernel and in recipes
+    pub fn code(self) -> u8 {
+        match self {
+            GenerateKeyType::LiskPassphrase => 0,
+            GenerateKeyType::PrivateKey => 1,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<GenerateKeyType> {
+        KEY_TYPES
+            .iter()
+            .cloned()
+            .find(|key_type| key_type.code() == code)
+    }
+
+    // Number of words of the generated secrets, 0 for keys that are not passphrases
+    pub fn words(self) -> usize {
+        match self {
+            GenerateKeyType::LiskPassphrase => PASSPHRASE_WORDS,
+            GenerateKeyType::PrivateKey => 0,
+        }
+    }
+
+    // The private key as shown to the user: the passphrase or the libsodium style secret key
+    pub fn private_key_string(self, key_material: &[u8; 32], public_key: &[u8; 32]) -> String {
+        match self {
+            GenerateKeyType::LiskPassphrase => {
+                String::from_utf8(entropy_to_mnemonic(cut_last_16(key_material))).unwrap()
+            }
+            GenerateKeyType::PrivateKey => format!(
+                "{}{}",
+                hex::encode_upper(key_material),
+                hex::encode_upper(public_key)
+            ),
+        }
+    }
+}
+
+// Number of words of generated passphrases
+pub const PASSPHRASE_WORDS: usize = 12;
+
+// BIP39: every word encodes 11 bits, of which 1 in 33 is checksum
+fn passphrase_entropy_bits(words: usize) -> usize {
+    words * 11 * 32 / 33
+}
+
+// Entropy of the generated secrets in bits
+pub fn entropy_bits(generate_key_type: GenerateKeyType) -> usize {
+    match generate_key_type {
+        GenerateKeyType::LiskPassphrase => passphrase_entropy_bits(generate_key_type.words()),
+        GenerateKeyType::PrivateKey => 256,
+    }
+}
+
 fn ed25519_privkey_to_pubkey(sec: &[u8; 32]) -> [u8; 32] {
     let secret_key = SecretKey::from_bytes(sec).unwrap();
     let public_key = PublicKey::from_secret::<Sha512>(&secret_key);
@@ -24,6 +91,8 @@ pub fn cut_last_16(indata: &[u8; 32]) -> &[u8; 16] {
 pub fn secret_to_pubkey(key_material: [u8; 32], generate_key_type: GenerateKeyType) -> [u8; 32] {
     match generate_key_type {
         GenerateKeyType::LiskPassphrase => {
+            // The private key is the hash of the mnemonic text, so the mnemonic cannot be
+            // skipped in the search loop. entropy_to_mnemonic is the fast bit level version.
             let mnemonic = entropy_to_mnemonic(cut_last_16(&key_material));
             let hash = Sha256::digest(&mnemonic);
             let hash_with_right_length = <&[u8; 32]>::try_from(&hash[0..32]).unwrap();
@@ -33,16 +102,152 @@ pub fn secret_to_pubkey(key_material: [u8; 32], generate_key_type: GenerateKeyTy
     }
 }
 
+// Key material (see `cut_last_16`) of a 12 word BIP39 mnemonic with single spaces between the
+// words, such that `secret_to_pubkey` hashes exactly the given text
+pub fn mnemonic_to_key_material(mnemonic: &str) -> Result<[u8; 32], String> {
+    let words: Vec<&str> = mnemonic.split(' ').collect();
+    if words.len() != PASSPHRASE_WORDS {
+        return Err(format!(
+            "Expected {} words separated by single spaces, got {}",
+            PASSPHRASE_WORDS,
+            words.len()
+        ));
+    }
+    let mut indices = Vec::with_capacity(PASSPHRASE_WORDS);
+    for word in words {
+        match WORDS.iter().position(|known| *known == word.as_bytes()) {
+            Some(index) => indices.push(index as u128),
+            None => return Err(format!("\"{}\" is not a BIP39 word", word)),
+        }
+    }
+    // 128 bits of entropy followed by a 4 bit checksum
+    let last = indices[PASSPHRASE_WORDS - 1];
+    let entropy = indices[..PASSPHRASE_WORDS - 1]
+        .iter()
+        .fold(0u128, |entropy, index| entropy << 11 | index)
+        << 7
+        | last >> 4;
+    let mut key_material = [0u8; 32];
+    key_material[16..].copy_from_slice(&entropy.to_be_bytes());
+    if Sha256::digest(cut_last_16(&key_material))[0] >> 4 != (last & 0xf) as u8 {
+        return Err("Invalid BIP39 checksum".to_string());
+    }
+    Ok(key_material)
+}
+
 pub fn pubkey_to_address(pubkey: &[u8; 32]) -> u64 {
     let hash = Sha256::digest(pubkey);
     let first_eight_bytes = <&[u8; 8]>::try_from(&hash[0..8]).unwrap();
     return u64::from_le_bytes(*first_eight_bytes);
 }
 
+// Every intermediate value of the derivation of `key_material` with a label, step by step with
+// the same primitives as `secret_to_pubkey` and `pubkey_to_address` (see --debug-derivation)
+pub fn derivation_trace(
+    key_material: [u8; 32],
+    generate_key_type: GenerateKeyType,
+) -> Vec<(&'static str, String)> {
+    let mut trace = vec![("Key material", hex::encode_upper(key_material))];
+    let seed = match generate_key_type {
+        GenerateKeyType::LiskPassphrase => {
+            let entropy = cut_last_16(&key_material);
+            trace.push(("BIP39 entropy", hex::encode_upper(entropy)));
+            let mnemonic = entropy_to_mnemonic(entropy);
+            trace.push(("Passphrase", String::from_utf8(mnemonic.clone()).unwrap()));
+            let hash = Sha256::digest(&mnemonic);
+            *<&[u8; 32]>::try_from(&hash[..]).unwrap()
+        }
+        GenerateKeyType::PrivateKey => key_material,
+    };
+    trace.push(("Ed25519 seed", hex::encode_upper(seed)));
+    let expanded = Sha512::digest(&seed);
+    trace.push(("SHA-512 of seed", hex::encode_upper(&expanded[..])));
+    let mut scalar = [0u8; 32];
+    scalar.copy_from_slice(&expanded[..32]);
+    scalar[0] &= 248;
+    scalar[31] &= 127;
+    scalar[31] |= 64;
+    trace.push(("Clamped scalar", hex::encode_upper(scalar)));
+    let public_key = ed25519_privkey_to_pubkey(&seed);
+    trace.push(("Public key", hex::encode_upper(public_key)));
+    let digest = Sha256::digest(&public_key);
+    trace.push(("SHA-256 of public key", hex::encode_upper(&digest[..])));
+    trace.push(("Address bytes (LE)", hex::encode_upper(&digest[..8])));
+    trace.push(("Address", format!("{}L", pubkey_to_address(&public_key))));
+    trace
+}
+
+// Slow reference versions of the mnemonic and address encoding for --strict-derivation.
+// They deliberately share no code with the fast versions above. Remove them once the
+// derivation is unified.
+
+// BIP39 as specified: the entropy bits followed by the first 4 bits of its SHA-256 hash,
+// read as 11 bit word indices
+fn reference_mnemonic(entropy: &[u8; 16]) -> String {
+    let checksum = Sha256::digest(entropy);
+    let bits: Vec<bool> = entropy
+        .iter()
+        .chain(checksum.iter().take(1))
+        .flat_map(|byte| (0..8).rev().map(move |bit| byte >> bit & 1 == 1))
+        .take(entropy.len() * 8 + entropy.len() / 4)
+        .collect();
+    let words: Vec<String> = bits
+        .chunks(11)
+        .map(|index_bits| {
+            let index = index_bits
+                .iter()
+                .fold(0, |index, &bit| index * 2 + bit as usize);
+            String::from_utf8(WORDS[index].to_vec()).unwrap()
+        })
+        .collect();
+    words.join(" ")
+}
+
+// The address as a decimal string: the first 8 bytes of the hash as a little endian number
+fn reference_address(pubkey: &[u8; 32]) -> String {
+    let hash = Sha256::digest(pubkey);
+    BigInt::from_bytes_le(Sign::Plus, &hash[..8]).to_string()
+}
+
+// Derives the public key and address of `key_material` via the reference versions and
+// compares them to the fast results. Returns a description of the first difference.
+pub fn check_derivation(
+    key_material: [u8; 32],
+    generate_key_type: GenerateKeyType,
+    public_key: [u8; 32],
+) -> Result<(), String> {
+    if generate_key_type == GenerateKeyType::LiskPassphrase {
+        let entropy = cut_last_16(&key_material);
+        let reference = reference_mnemonic(entropy);
+        if reference.as_bytes() != &entropy_to_mnemonic(entropy)[..] {
+            return Err("the passphrase differs from the reference BIP39 encoding".to_string());
+        }
+        let hash = Sha256::digest(reference.as_bytes());
+        if ed25519_privkey_to_pubkey(<&[u8; 32]>::try_from(&hash[..]).unwrap()) != public_key {
+            return Err(
+                "the public key differs from the one of the reference passphrase".to_string(),
+            );
+        }
+    } else if ed25519_privkey_to_pubkey(&key_material) != public_key {
+        return Err("the public key differs from the one of the key pair seed".to_string());
+    }
+    let address = pubkey_to_address(&public_key).to_string();
+    let reference = reference_address(&public_key);
+    if address != reference {
+        return Err(format!(
+            "address {}L differs from the reference address {}L",
+            address, reference
+        ));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     // importing names from outer (for mod tests) scope.
     use super::*;
+    use rand::{Rng, SeedableRng, XorShiftRng};
+    use std::collections::HashSet;
 
     #[test]
     fn test_ed25519_secret_to_pubkey() {
@@ -79,6 +284,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_entropy_bits() {
+        assert_eq!(passphrase_entropy_bits(12), 128);
+        assert_eq!(passphrase_entropy_bits(24), 256);
+        assert_eq!(entropy_bits(GenerateKeyType::LiskPassphrase), 128);
+        assert_eq!(entropy_bits(GenerateKeyType::PrivateKey), 256);
+        // the passphrase is built from the last 16 bytes of the key material
+        assert_eq!(cut_last_16(&[0u8; 32]).len() * 8, 128);
+    }
+
+    #[test]
+    fn test_key_type_code() {
+        for &key_type in KEY_TYPES.iter() {
+            assert_eq!(GenerateKeyType::from_code(key_type.code()), Some(key_type));
+        }
+        assert_eq!(GenerateKeyType::from_code(2), None);
+    }
+
     #[test]
     fn test_pubkey_to_address() {
         // https://testnet-explorer.lisk.io/address/6076671634347365051L
@@ -89,4 +312,130 @@ mod tests {
         );
         assert_eq!(pubkey_to_address(&pubkey), 6076671634347365051u64);
     }
+
+    #[test]
+    fn test_reference_derivation() {
+        // Fixed seed such that failures are reproducible
+        let mut rng = XorShiftRng::from_seed([0x4c69, 0x736b, 0x7661, 0x6e69]);
+        for _ in 0..100 {
+            let mut key_material = [0u8; 32];
+            rng.fill_bytes(&mut key_material);
+            let entropy = cut_last_16(&key_material);
+            assert_eq!(
+                reference_mnemonic(entropy).into_bytes(),
+                entropy_to_mnemonic(entropy)
+            );
+            for &generate_key_type in KEY_TYPES.iter() {
+                let public_key = secret_to_pubkey(key_material, generate_key_type);
+                assert_eq!(
+                    reference_address(&public_key),
+                    pubkey_to_address(&public_key).to_string()
+                );
+                assert_eq!(
+                    check_derivation(key_material, generate_key_type, public_key),
+                    Ok(())
+                );
+                let mut wrong = public_key;
+                wrong[0] ^= 1;
+                assert!(check_derivation(key_material, generate_key_type, wrong).is_err());
+            }
+        }
+        // BIP39 test vector
+        assert_eq!(
+            reference_mnemonic(&[0u8; 16]),
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+        );
+    }
+
+    #[test]
+    fn test_derivation_trace() {
+        let trace = derivation_trace([0u8; 32], GenerateKeyType::LiskPassphrase);
+        assert_eq!(trace[0], ("Key material", "00".repeat(32)));
+        assert_eq!(
+            trace[2],
+            (
+                "Passphrase",
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about".to_string()
+            )
+        );
+        let mut key_material = [0u8; 32];
+        key_material[0] = 0x4c;
+        for &generate_key_type in KEY_TYPES.iter() {
+            let trace = derivation_trace(key_material, generate_key_type);
+            let public_key = secret_to_pubkey(key_material, generate_key_type);
+            let value = |label| trace.iter().find(|step| step.0 == label).unwrap().1.clone();
+            assert_eq!(value("Public key"), hex::encode_upper(public_key));
+            assert_eq!(
+                value("Address"),
+                format!("{}L", pubkey_to_address(&public_key))
+            );
+        }
+        // RFC 8032 test vector 1
+        let mut seed = [0u8; 32];
+        hex::decode_to_slice(
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+            &mut seed,
+        )
+        .unwrap();
+        let trace = derivation_trace(seed, GenerateKeyType::PrivateKey);
+        assert_eq!(
+            trace[4],
+            (
+                "Public key",
+                "D75A980182B10AB7D54BFED3C964073A0EE172F3DAA62325AF021A68F707511A".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_mnemonic_to_key_material() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        assert_eq!(mnemonic_to_key_material(mnemonic), Ok([0u8; 32]));
+
+        let mut rng = XorShiftRng::from_seed([0x4c69, 0x736b, 0x7661, 0x6e69]);
+        for _ in 0..100 {
+            let mut key_material = [0u8; 32];
+            rng.fill_bytes(&mut key_material[16..]);
+            let mnemonic =
+                String::from_utf8(entropy_to_mnemonic(cut_last_16(&key_material))).unwrap();
+            assert_eq!(mnemonic_to_key_material(&mnemonic), Ok(key_material));
+        }
+
+        // wrong checksum
+        assert!(mnemonic_to_key_material(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon"
+        )
+        .is_err());
+        assert!(mnemonic_to_key_material(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon lisk"
+        )
+        .is_err());
+        assert!(mnemonic_to_key_material(
+            "abandon  abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+        )
+        .is_err());
+        assert!(mnemonic_to_key_material("abandon about").is_err());
+    }
+
+    #[test]
+    fn test_secret_to_pubkey_no_trivial_collisions() {
+        // Fixed seed such that failures are reproducible
+        let mut rng = XorShiftRng::from_seed([0x4c69, 0x736b, 0x7661, 0x6e69]);
+        for &generate_key_type in &[GenerateKeyType::PrivateKey, GenerateKeyType::LiskPassphrase] {
+            let mut keys = HashSet::new();
+            let mut pubkeys = HashSet::new();
+            while keys.len() < 200 {
+                let mut key_material = [0u8; 32];
+                rng.fill_bytes(&mut key_material);
+                // For passphrases only the last 16 bytes are used
+                if !keys.insert(*cut_last_16(&key_material)) {
+                    continue;
+                }
+                let pubkey = secret_to_pubkey(key_material, generate_key_type);
+                assert_eq!(secret_to_pubkey(key_material, generate_key_type), pubkey);
+                assert!(pubkeys.insert(pubkey));
+            }
+            assert!(!pubkeys.contains(&[0u8; 32]));
+        }
+    }
 }
@@ -0,0 +1,138 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::process;
+
+#[cfg(feature = "gpu")]
+use gpu::GpuError;
+
+#[derive(Debug)]
+pub enum Error {
+    // Invalid command line arguments
+    Argument(String),
+    // The user declined to continue (see --confirm-secrets)
+    Cancelled,
+    // No match within the attempt budget (see --max-attempts). Contains the number of keys tried.
+    AttemptsExhausted(u64),
+    #[cfg(feature = "gpu")]
+    Gpu(GpuError),
+    #[cfg(not(feature = "gpu"))]
+    GpuUnavailable,
+    Io(io::Error),
+    // The fast and the reference derivation disagree (see --strict-derivation)
+    DerivationMismatch(String),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Argument(_) => 2,
+            Error::Cancelled => 1,
+            Error::AttemptsExhausted(_) => 1,
+            #[cfg(feature = "gpu")]
+            Error::Gpu(_) => 3,
+            #[cfg(not(feature = "gpu"))]
+            Error::GpuUnavailable => 3,
+            Error::Io(_) => 4,
+            Error::DerivationMismatch(_) => 5,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Argument(message) => write!(f, "{}", message),
+            Error::Cancelled => write!(f, "Cancelled"),
+            Error::AttemptsExhausted(tried) => write!(
+                f,
+                "Attempt budget exhausted after {} keys without a match",
+                tried
+            ),
+            #[cfg(feature = "gpu")]
+            Error::Gpu(error) => write!(f, "GPU error: {}", error),
+            #[cfg(not(feature = "gpu"))]
+            Error::GpuUnavailable => write!(
+                f,
+                "GPU support has been disabled at compile time. Rebuild with \"--features gpu\" to enable GPU support."
+            ),
+            Error::Io(error) => write!(f, "I/O error: {}", error),
+            Error::DerivationMismatch(message) => write!(
+                f,
+                "Derivation mismatch: {}. Please report this, the found key must not be used.",
+                message
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+#[cfg(feature = "gpu")]
+impl From<GpuError> for Error {
+    fn from(error: GpuError) -> Error {
+        Error::Gpu(error)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+// Reports the error on stderr and exits with its exit code
+pub fn exit_with_error(error: &Error) -> ! {
+    eprintln!("Error: {}", error);
+    process::exit(error.exit_code());
+}
+
+// Parses a command line value, naming the argument in the error message
+pub fn parse_value<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, Error> {
+    value
+        .parse()
+        .map_err(|_| Error::Argument(format!("Invalid value \"{}\" for {}", value, name)))
+}
+
+// Parses a command line value with a parser reporting its own message, prefixed by the argument
+pub fn parse_argument<T, F: FnOnce(&str) -> Result<T, String>>(
+    value: &str,
+    name: &str,
+    parse: F,
+) -> Result<T, Error> {
+    parse(value).map_err(|error| Error::Argument(format!("Invalid {}: {}", name, error)))
+}
+
+#[cfg(test)]
+mod tests {
+    // importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(parse_value::<usize>("12", "limit").unwrap(), 12);
+        match parse_value::<usize>("-1", "limit") {
+            Err(Error::Argument(message)) => assert_eq!(message, "Invalid value \"-1\" for limit"),
+            _ => panic!("expected argument error"),
+        }
+    }
+
+    #[test]
+    fn test_parse_argument() {
+        let parse = |value: &str| value.parse::<u8>().map_err(|_| "too big".to_string());
+        assert_eq!(parse_argument("12", "--count", parse).unwrap(), 12);
+        match parse_argument("300", "--count", parse) {
+            Err(Error::Argument(message)) => assert_eq!(message, "Invalid --count: too big"),
+            _ => panic!("expected argument error"),
+        }
+    }
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(Error::Argument(String::new()).exit_code(), 2);
+        assert_eq!(Error::Cancelled.exit_code(), 1);
+        assert_eq!(Error::DerivationMismatch(String::new()).exit_code(), 5);
+        let io_error = io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe");
+        assert_eq!(Error::from(io_error).exit_code(), 4);
+    }
+}
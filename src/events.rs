@@ -0,0 +1,143 @@
+use std::fs::File;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::io::FromRawFd;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use error::Error;
+
+// Version of the event lines of --events-fd, increased on incompatible changes. Every line is
+// a JSON object with "version", "event", "time" (seconds since the Unix epoch) and the fields
+// of the event:
+//
+//   started    threads (CPU), gpu
+//   estimate   pattern, expectedAttempts, lowerBound (true if expectedAttempts is only a lower bound)
+//   gpu_ready  threads
+//   progress   attempts, keysPerSec
+//   found      address, publicKey (never the private key), goals (the matched patterns file lines,
+//              only with --patterns-file)
+//   stopped    reason ("limit", "budget", "interrupted" or "closed" for a closed stdout), attempts, found
+pub const EVENTS_VERSION: u32 = 1;
+
+// Sends lifecycle events as JSON lines. Clones share the writer.
+#[derive(Clone)]
+pub struct Events {
+    writer: Option<Arc<Mutex<Box<dyn Write + Send>>>>,
+}
+
+impl Events {
+    pub fn disabled() -> Events {
+        Events { writer: None }
+    }
+
+    pub fn new(writer: Box<dyn Write + Send>) -> Events {
+        Events {
+            writer: Some(Arc::new(Mutex::new(writer))),
+        }
+    }
+
+    // Writes to the already open file descriptor `fd`, e.g. a pipe set up by the caller
+    #[cfg(unix)]
+    pub fn to_fd(fd: i32) -> Result<Events, Error> {
+        if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+            return Err(Error::Argument(format!(
+                "--events-fd {} is not an open file descriptor",
+                fd
+            )));
+        }
+        let file = unsafe { File::from_raw_fd(fd) };
+        Ok(Events::new(Box::new(file)))
+    }
+
+    #[cfg(not(unix))]
+    pub fn to_fd(_fd: i32) -> Result<Events, Error> {
+        Err(Error::Argument(
+            "--events-fd is only supported on Unix".to_string(),
+        ))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    // `fields` are JSON object members like "\"attempts\":5". Write errors are ignored, since
+    // a consumer that went away must not stop the search.
+    pub fn emit(&self, event: &str, fields: &str) {
+        if let Some(ref writer) = self.writer {
+            let time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs_f64())
+                .unwrap_or(0.0);
+            let separator = if fields.is_empty() { "" } else { "," };
+            let mut writer = writer.lock().unwrap();
+            writeln!(
+                writer,
+                "{{\"version\":{},\"event\":\"{}\",\"time\":{:.3}{}{}}}",
+                EVENTS_VERSION, event, time, separator, fields
+            )
+            .and_then(|_| writer.flush())
+            .ok();
+        }
+    }
+}
+
+// `value` as a JSON string literal
+pub fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    // importing names from outer (for mod tests) scope.
+    use super::*;
+    use std::io;
+
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(data)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_emit() {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let events = Events::new(Box::new(SharedBuffer(buffer.clone())));
+        events.emit("started", "\"threads\":2,\"gpu\":false");
+        events.clone().emit("stopped", "");
+        let output = String::from_utf8(buffer.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("{\"version\":1,\"event\":\"started\",\"time\":"));
+        assert!(lines[0].ends_with(",\"threads\":2,\"gpu\":false}"));
+        assert!(lines[1].starts_with("{\"version\":1,\"event\":\"stopped\",\"time\":"));
+        assert!(!lines[1].contains(",}"));
+
+        // does nothing
+        Events::disabled().emit("started", "");
+        assert!(!Events::disabled().is_enabled());
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("max length 12"), "\"max length 12\"");
+        assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\u000a\"");
+    }
+}
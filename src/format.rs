@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+// Inserts thousands separators into a decimal number, e.g. "1844674.5" -> "1,844,674.5"
+pub fn group_thousands(number: &str) -> String {
+    let (integer_part, rest) = match number.find('.') {
+        Some(pos) => number.split_at(pos),
+        None => (number, ""),
+    };
+    let (sign, digits) = if integer_part.starts_with('-') {
+        integer_part.split_at(1)
+    } else {
+        ("", integer_part)
+    };
+
+    let mut out = String::with_capacity(number.len() + digits.len() / 3);
+    out.push_str(sign);
+    for (i, digit) in digits.chars().enumerate() {
+        if i != 0 && (digits.len() - i) % 3 == 0 {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out.push_str(rest);
+    out
+}
+
+// Parses a duration like "90", "90s", "5m" or "2h". Plain numbers are seconds.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let (number, multiplier) = match input.chars().last() {
+        Some('s') => (&input[..input.len() - 1], 1),
+        Some('m') => (&input[..input.len() - 1], 60),
+        Some('h') => (&input[..input.len() - 1], 60 * 60),
+        _ => (input, 1),
+    };
+    let value: u64 = number
+        .parse()
+        .map_err(|_| format!("Invalid duration \"{}\"", input))?;
+    value
+        .checked_mul(multiplier)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("Duration \"{}\" is too long", input))
+}
+
+#[cfg(test)]
+mod tests {
+    // importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_group_thousands() {
+        assert_eq!(group_thousands("0"), "0");
+        assert_eq!(group_thousands("999"), "999");
+        assert_eq!(group_thousands("1000"), "1,000");
+        assert_eq!(group_thousands("1844674"), "1,844,674");
+        assert_eq!(
+            group_thousands("18446744073709551615"),
+            "18,446,744,073,709,551,615"
+        );
+        assert_eq!(group_thousands("41497.1"), "41,497.1");
+        assert_eq!(group_thousands("123.45"), "123.45");
+        assert_eq!(group_thousands("-1234567"), "-1,234,567");
+        assert_eq!(group_thousands(""), "");
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("0"), Ok(Duration::from_secs(0)));
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("-5s").is_err());
+        assert_eq!(
+            parse_duration("5124095576030431h"),
+            Ok(Duration::from_secs(5124095576030431 * 3600))
+        );
+        assert_eq!(
+            parse_duration("5124095576030432h"),
+            Err("Duration \"5124095576030432h\" is too long".to_string())
+        );
+    }
+}
@@ -1,24 +1,150 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
 use ocl::builders::ProgramBuilder;
-use ocl::enums::DeviceInfo;
+use ocl::enums::{DeviceInfo, DeviceInfoResult};
 use ocl::flags::MemFlags;
 use ocl::Buffer;
 use ocl::Platform;
-use ocl::{Context, Device, Kernel, Queue};
+use ocl::{Context, Device, Kernel, Queue, SpatialDims};
 
 use derivation::GenerateKeyType;
 
-fn convert_ocl_error(error: ocl::Error) -> String {
-    return error.to_string();
+#[derive(Debug)]
+pub enum GpuError {
+    NoPlatform,
+    NoSuchPlatform { index: usize, count: usize },
+    NoDevice { platform: usize, name: String },
+    KernelBuild(String),
+    Enqueue(String),
+    Other(String),
 }
 
-fn convert_ocl_core_error(error: ocl::OclCoreError) -> String {
-    return error.to_string();
+impl fmt::Display for GpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GpuError::NoPlatform => write!(
+                f,
+                "No OpenCL platforms exist (check your drivers and OpenCL setup)"
+            ),
+            GpuError::NoSuchPlatform { index, count } => {
+                write!(f, "Platform index {} too large (max {})", index, count - 1)
+            }
+            GpuError::NoDevice { platform, name } => write!(
+                f,
+                "GPU platform {} ({}) has no OpenCL devices. Select another one with --gpu-platform.",
+                platform, name
+            ),
+            GpuError::KernelBuild(message) => write!(f, "Failed to build GPU kernel: {}", message),
+            GpuError::Enqueue(message) => write!(f, "Failed to run GPU command: {}", message),
+            GpuError::Other(message) => write!(f, "{}", message),
+        }
+    }
 }
 
+impl Error for GpuError {}
+
+impl From<ocl::Error> for GpuError {
+    fn from(error: ocl::Error) -> GpuError {
+        GpuError::Other(error.to_string())
+    }
+}
+
+impl From<ocl::OclCoreError> for GpuError {
+    fn from(error: ocl::OclCoreError) -> GpuError {
+        GpuError::Other(error.to_string())
+    }
+}
+
+fn enqueue_error(error: ocl::Error) -> GpuError {
+    GpuError::Enqueue(error.to_string())
+}
+
+fn kernel_build_error(error: ocl::Error) -> GpuError {
+    GpuError::KernelBuild(error.to_string())
+}
+
+// Duration of a kernel launch aimed at with --gpu-auto-batch
+pub const AUTO_BATCH_TARGET: Duration = Duration::from_millis(100);
+// Number of launches the batch size is tuned over with --gpu-auto-batch
+pub const AUTO_BATCH_ROUNDS: usize = 8;
+
 pub struct Gpu {
     kernel: ocl::Kernel,
     result: Buffer<u8>,
     key_root: Buffer<u8>,
+    threads: usize,
+    local_work_size: Option<usize>,
+    // The largest global work size the device supports
+    max_threads: usize,
+    // Launches left to tune the batch size over (see --gpu-auto-batch)
+    tuning_rounds: usize,
+}
+
+// The batch size that takes about `target` if `threads` took `elapsed`. Changes by at most a
+// factor of 4 per launch to damp outliers and stays a multiple of the local work size.
+fn tuned_batch_size(
+    threads: usize,
+    elapsed: Duration,
+    target: Duration,
+    local_work_size: Option<usize>,
+    max_threads: usize,
+) -> usize {
+    let factor = (target.as_secs_f64() / elapsed.as_secs_f64().max(1e-6)).clamp(0.25, 4.0);
+    let granularity = local_work_size.unwrap_or(1);
+    let tuned = ((threads as f64 * factor) as usize).min(max_threads);
+    (tuned / granularity * granularity).max(granularity)
+}
+
+// Fits the requested work sizes into the device limits. The global work size must fit into
+// the device's size_t and be a multiple of the local work size. Returns warnings for every
+// adjustment.
+fn fit_work_sizes(
+    threads: usize,
+    local_work_size: Option<usize>,
+    max_work_group_size: usize,
+    address_bits: u32,
+) -> (usize, Option<usize>, Vec<String>) {
+    let mut warnings = Vec::new();
+    let local_work_size = local_work_size.map(|local| {
+        if local > max_work_group_size {
+            warnings.push(format!(
+                "--gpu-local-work-size {} exceeds the device maximum of {}. Using {}.",
+                local, max_work_group_size, max_work_group_size
+            ));
+            max_work_group_size
+        } else {
+            local
+        }
+    });
+    let max_threads = if address_bits >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << address_bits) - 1
+    };
+    let mut fitted = threads;
+    if fitted as u64 > max_threads {
+        fitted = max_threads as usize;
+    }
+    if let Some(local) = local_work_size {
+        fitted = (fitted / local * local).max(local);
+    }
+    if fitted != threads {
+        warnings.push(format!(
+            "--gpu-threads {} is not supported by the device ({} address bits{}). Using {}.",
+            threads,
+            address_bits,
+            match local_work_size {
+                Some(local) => format!(", multiple of the local work size {}", local),
+                None => String::new(),
+            },
+            fitted
+        ));
+    }
+    (fitted, local_work_size, warnings)
 }
 
 impl Gpu {
@@ -29,7 +155,8 @@ impl Gpu {
         local_work_size: Option<usize>,
         max_address_value: u64,
         generate_key_type: GenerateKeyType,
-    ) -> Result<Gpu, String> {
+        build_options: &str,
+    ) -> Result<Gpu, GpuError> {
         let mut program_builder = ProgramBuilder::new();
         program_builder
             .src(include_str!("opencl/types.cl"))
@@ -43,42 +170,52 @@ impl Gpu {
             .src(include_str!("opencl/bip39.cl"))
             .src(include_str!("opencl/lisk.cl"))
             .src(include_str!("opencl/entry.cl"));
+        if !build_options.is_empty() {
+            program_builder.cmplr_opt(build_options);
+        }
 
         let platforms = Platform::list();
-        if platforms.len() == 0 {
-            return Err("No OpenCL platforms exist (check your drivers and OpenCL setup)".into());
+        if platforms.is_empty() {
+            return Err(GpuError::NoPlatform);
         }
         if platform_idx >= platforms.len() {
-            return Err(format!(
-                "Platform index {} too large (max {})",
-                platform_idx,
-                platforms.len() - 1
-            )
-            .into());
+            return Err(GpuError::NoSuchPlatform {
+                index: platform_idx,
+                count: platforms.len(),
+            });
         }
 
         let platform = platforms[platform_idx];
         eprintln!("GPU platform {} {}", platform.vendor()?, platform.name()?);
 
-        let device = Device::by_idx_wrap(platform, device_idx).expect("Requested device not found");
+        // Some drivers report CL_DEVICE_NOT_FOUND instead of an empty list
+        let devices = Device::list_all(platform).unwrap_or_default();
+        if devices.is_empty() {
+            return Err(GpuError::NoDevice {
+                platform: platform_idx,
+                name: platform.name()?,
+            });
+        }
+        // Like Device::by_idx_wrap
+        let device = devices[device_idx % devices.len()];
         eprintln!(
             "Using GPU device {} {}, OpenCL {}",
-            device.vendor().map_err(convert_ocl_error)?,
-            device.name().map_err(convert_ocl_error)?,
-            device.version().map_err(convert_ocl_core_error)?
-        );
-        eprintln!(
-            "Address bits {}",
-            device
-                .info(DeviceInfo::AddressBits)
-                .map_err(convert_ocl_error)?
-        );
-        eprintln!(
-            "MaxWorkGroupSize {}",
-            device
-                .info(DeviceInfo::MaxWorkGroupSize)
-                .map_err(convert_ocl_error)?
+            device.vendor()?,
+            device.name()?,
+            device.version()?
         );
+        let address_bits = match device.info(DeviceInfo::AddressBits)? {
+            DeviceInfoResult::AddressBits(bits) => bits,
+            _ => 32,
+        };
+        let max_work_group_size = device.max_wg_size()?;
+        eprintln!("Address bits {}", address_bits);
+        eprintln!("MaxWorkGroupSize {}", max_work_group_size);
+        let (threads, local_work_size, warnings) =
+            fit_work_sizes(threads, local_work_size, max_work_group_size, address_bits);
+        for warning in warnings {
+            eprintln!("Warning: {}", warning);
+        }
 
         let context = Context::builder()
             .platform(platform)
@@ -89,8 +226,21 @@ impl Gpu {
         let queue = Queue::new(&context, device, None)?;
         eprintln!("GPU queue created.");
 
-        let program = program_builder.devices(device).build(&context)?;
-        eprintln!("GPU program successfully compiled.");
+        // Compiling can take a while, so users should not think the program hangs
+        if !build_options.is_empty() {
+            eprintln!("GPU build options: {}", build_options);
+        }
+        eprint!("Building GPU kernel for device {}...", device.name()?);
+        io::stderr().flush().ok();
+        let build_start = Instant::now();
+        let program = program_builder
+            .devices(device)
+            .build(&context)
+            .map_err(|error| {
+                eprintln!(" failed");
+                kernel_build_error(error)
+            })?;
+        eprintln!(" done ({:.1}s)", build_start.elapsed().as_secs_f64());
 
         let result = Buffer::<u8>::builder()
             .queue(queue.clone())
@@ -104,10 +254,7 @@ impl Gpu {
             .len(32)
             .build()?;
 
-        let gen_key_type_code: u8 = match generate_key_type {
-            GenerateKeyType::LiskPassphrase => 0,
-            GenerateKeyType::PrivateKey => 1,
-        };
+        let gen_key_type_code = generate_key_type.code();
 
         let kernel = {
             let mut builder = Kernel::builder();
@@ -123,7 +270,7 @@ impl Gpu {
             if let Some(local_work_size) = local_work_size {
                 builder.local_work_size(local_work_size);
             }
-            builder.build()?
+            builder.build().map_err(kernel_build_error)?
         };
 
         eprintln!("GPU kernel built.");
@@ -132,29 +279,88 @@ impl Gpu {
             kernel,
             result,
             key_root,
+            threads,
+            local_work_size,
+            max_threads: fit_work_sizes(
+                usize::MAX,
+                local_work_size,
+                max_work_group_size,
+                address_bits,
+            )
+            .0,
+            tuning_rounds: 0,
         })
     }
 
-    pub fn compute(&mut self, key_root: &[u8]) -> Result<Option<[u8; 32]>, String> {
+    // Tunes the batch size over the next `AUTO_BATCH_ROUNDS` launches such that they take about
+    // `AUTO_BATCH_TARGET` (see --gpu-auto-batch)
+    pub fn enable_auto_batch(&mut self) {
+        self.tuning_rounds = AUTO_BATCH_ROUNDS;
+    }
+
+    fn tune_batch_size(&mut self, elapsed: Duration) {
+        let threads = tuned_batch_size(
+            self.threads,
+            elapsed,
+            AUTO_BATCH_TARGET,
+            self.local_work_size,
+            self.max_threads,
+        );
+        if threads != self.threads {
+            self.kernel
+                .set_default_global_work_size(SpatialDims::One(threads));
+            self.threads = threads;
+        }
+        self.tuning_rounds -= 1;
+        if self.tuning_rounds == 0 {
+            eprintln!("GPU batch size tuned to {} threads", self.threads);
+        }
+    }
+
+    // The number of keys tried per `compute` call, after fitting into the device limits
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn set_max_address_value(&mut self, max_address_value: u64) -> Result<(), GpuError> {
+        self.kernel.set_arg(2, max_address_value)?;
+        Ok(())
+    }
+
+    pub fn compute(&mut self, key_root: &[u8]) -> Result<Option<[u8; 32]>, GpuError> {
         debug_assert!({
             // Ensure result is filled with zeros
             let mut result = [0u8; 32];
-            self.result.read(&mut result as &mut [u8]).enq()?;
+            self.result
+                .read(&mut result as &mut [u8])
+                .enq()
+                .map_err(enqueue_error)?;
             result.iter().all(|&b| b == 0)
         });
 
-        self.key_root.write(key_root).enq()?;
+        let launch_start = Instant::now();
+        self.key_root.write(key_root).enq().map_err(enqueue_error)?;
         unsafe {
-            self.kernel.enq()?;
+            self.kernel.enq().map_err(enqueue_error)?;
         }
 
         let mut out = [0u8; 32];
-        self.result.read(&mut out as &mut [u8]).enq()?;
+        self.result
+            .read(&mut out as &mut [u8])
+            .enq()
+            .map_err(enqueue_error)?;
+        // Reading the result waits for the kernel
+        if self.tuning_rounds > 0 {
+            self.tune_batch_size(launch_start.elapsed());
+        }
 
         let matched = !out.iter().all(|&b| b == 0);
         if matched {
             let zeros = [0u8; 32];
-            self.result.write(&zeros as &[u8]).enq()?;
+            self.result
+                .write(&zeros as &[u8])
+                .enq()
+                .map_err(enqueue_error)?;
             return Ok(Option::Some(out));
         } else {
             return Ok(Option::None);
@@ -168,6 +374,75 @@ mod tests {
     use super::*;
     use pubkey_matcher::max_address;
 
+    #[test]
+    fn test_tuned_batch_size() {
+        let target = Duration::from_millis(100);
+        let tuned = |threads, millis, local| {
+            tuned_batch_size(
+                threads,
+                Duration::from_millis(millis),
+                target,
+                local,
+                1 << 32,
+            )
+        };
+        assert_eq!(tuned(1 << 20, 50, None), 1 << 21);
+        assert_eq!(tuned(1 << 20, 200, Some(256)), 1 << 19);
+        assert_eq!(tuned(1 << 20, 100, Some(256)), 1 << 20);
+        // at most a factor of 4 per launch
+        assert_eq!(tuned(1 << 20, 1, None), 1 << 22);
+        assert_eq!(tuned(1 << 20, 0, None), 1 << 22);
+        assert_eq!(tuned(1 << 20, 10_000, None), 1 << 18);
+        // multiple of the local work size, at least one work group
+        assert_eq!(tuned(1000, 300, Some(256)), 256);
+        assert_eq!(tuned(256, 10_000, Some(256)), 256);
+        assert_eq!(tuned(1, 10_000, None), 1);
+        // within the device limit
+        assert_eq!(
+            tuned_batch_size(
+                1 << 31,
+                Duration::from_millis(10),
+                target,
+                None,
+                (1 << 32) - 1
+            ),
+            (1 << 32) - 1
+        );
+    }
+
+    #[test]
+    fn test_fit_work_sizes() {
+        let (threads, local, warnings) = fit_work_sizes(1 << 20, Some(256), 1024, 64);
+        assert_eq!((threads, local), (1 << 20, Some(256)));
+        assert!(warnings.is_empty());
+
+        let (threads, local, warnings) = fit_work_sizes(1 << 20, Some(2048), 1024, 64);
+        assert_eq!((threads, local), (1 << 20, Some(1024)));
+        assert_eq!(warnings.len(), 1);
+
+        let (threads, local, warnings) = fit_work_sizes(1 << 40, None, 1024, 32);
+        assert_eq!((threads, local), ((1 << 32) - 1, None));
+        assert_eq!(warnings.len(), 1);
+
+        let (threads, _, warnings) = fit_work_sizes(1000, Some(256), 1024, 64);
+        assert_eq!(threads, 768);
+        assert_eq!(warnings.len(), 1);
+        let (threads, _, _) = fit_work_sizes(100, Some(256), 1024, 64);
+        assert_eq!(threads, 256);
+    }
+
+    #[test]
+    fn test_no_device_message() {
+        let error = GpuError::NoDevice {
+            platform: 1,
+            name: "Portable Computing Language".to_string(),
+        };
+        assert_eq!(
+            error.to_string(),
+            "GPU platform 1 (Portable Computing Language) has no OpenCL devices. Select another one with --gpu-platform."
+        );
+    }
+
     #[test]
     fn test_finds_private_key_directly() {
         let gpu_platform = 0;
@@ -182,6 +457,7 @@ mod tests {
             gpu_local_work_size,
             max_address(max_length),
             GenerateKeyType::PrivateKey,
+            "",
         )
         .unwrap();
 
@@ -226,6 +502,7 @@ mod tests {
             gpu_local_work_size,
             max_address(max_length),
             GenerateKeyType::PrivateKey,
+            "",
         )
         .unwrap();
 
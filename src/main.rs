@@ -1,36 +1,95 @@
-use std::f64;
+use std::fs;
+use std::hint;
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
 use std::process;
 use std::sync::atomic;
-use std::sync::atomic::AtomicUsize;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+extern crate atty;
 extern crate clap;
 extern crate digest;
 extern crate ed25519_dalek;
 extern crate hex;
+#[cfg(unix)]
+extern crate libc;
 extern crate num_bigint;
+use num_bigint::BigInt;
 extern crate num_cpus;
 extern crate sha2;
 
 extern crate rand;
-use rand::{OsRng, Rng};
+use rand::Rng;
 
 extern crate num_traits;
-use num_traits::ToPrimitive;
 
 #[cfg(feature = "gpu")]
 extern crate ocl;
 
+mod checkpoint;
+use checkpoint::{
+    load_checkpoint, save_checkpoint, StateFile, PUBLISH_INTERVAL, STATE_SAVE_INTERVAL,
+};
+
 mod cpu;
-use cpu::bip39::entropy_to_mnemonic;
+
+mod error;
+use error::{exit_with_error, parse_argument, parse_value, Error};
+
+mod events;
+use events::{json_string, Events};
+
+mod format;
+use format::{group_thousands, parse_duration};
 
 mod derivation;
-use derivation::{cut_last_16, pubkey_to_address, secret_to_pubkey, GenerateKeyType};
+use derivation::{
+    check_derivation, derivation_trace, mnemonic_to_key_material, pubkey_to_address,
+    secret_to_pubkey, GenerateKeyType, KEY_TYPES,
+};
 
 mod pubkey_matcher;
-use pubkey_matcher::{max_address, PubkeyMatcher};
+use pubkey_matcher::{
+    hash_leading_zeros, length_band, like_conditions, lucky_patterns, parse_address,
+    parse_contains, parse_digit_count_rules, parse_patterns_file, parse_prefix, parse_suffix,
+    regex_condition, Condition, Grouping, Monotonic, Pattern, PubkeyMatcher, CONDITION_KEYWORDS,
+    MAX_ADDRESS_LENGTH,
+};
+
+mod output;
+use output::{
+    connect_socket, full_address, output_solutions, print_state_totals, print_summary,
+    print_time_summary, send_solution, Backpressure, DifficultyTiers, OutputOptions, OutputParams,
+    Solution, SolutionKind, MAX_SORTED_OUTPUT, OUTPUT_FORMATS, SOLUTION_JSON_SCHEMA,
+    SOLUTION_QUEUE_SIZE,
+};
+
+mod rng;
+use rng::{new_rng, seeded_rng, RngSource};
+
+mod recipe;
+use recipe::Recipe;
+
+mod regex;
+
+mod record;
+use record::{load_record, save_record, Record, ThreadRecord};
+
+mod reproduce;
+use reproduce::{parse_simple_output_line, reproduce};
+
+mod swap;
+use swap::swap_active;
+
+mod usage;
+
+mod walker;
+use walker::KeySpaceWalker;
 
 #[cfg(feature = "gpu")]
 mod gpu;
@@ -49,83 +108,594 @@ impl Gpu {
         _local_work_size: Option<usize>,
         _max_address_value: u64,
         _generate_key_type: GenerateKeyType,
-    ) -> Result<Gpu, String> {
-        eprintln!("GPU support has been disabled at compile time.");
-        eprintln!("Rebuild with \"--features gpu\" to enable GPU support.");
-        process::exit(1);
+        _build_options: &str,
+    ) -> Result<Gpu, Error> {
+        Err(Error::GpuUnavailable)
+    }
+
+    pub fn set_max_address_value(&mut self, _max_address_value: u64) -> Result<(), Error> {
+        unreachable!()
     }
 
-    pub fn compute(&mut self, _key_root: &[u8]) -> Result<Option<[u8; 32]>, String> {
+    pub fn threads(&self) -> usize {
+        unreachable!()
+    }
+
+    pub fn enable_auto_batch(&mut self) {
+        unreachable!()
+    }
+
+    pub fn compute(&mut self, _key_root: &[u8]) -> Result<Option<[u8; 32]>, Error> {
         unreachable!()
     }
 }
 
-fn print_solution(
-    secret_key_material: [u8; 32],
-    secret_key_type: GenerateKeyType,
-    public_key: [u8; 32],
-    simple_output: bool,
-) {
-    if simple_output {
-        println!(
-            "{} {}",
-            hex::encode_upper(&secret_key_material as &[u8]),
-            pubkey_to_address(&public_key),
+struct ThreadParams {
+    attempts: Arc<AtomicU64>,
+    // Whether `attempts` needs to be updated (progress output or attempt budget)
+    count_attempts: bool,
+    max_attempts: Option<u64>,
+    // Set to make all workers exit
+    stop: Arc<AtomicBool>,
+    // Set while the search is paused by the user
+    paused: Arc<AtomicBool>,
+    generate_key_type: GenerateKeyType,
+    matcher: Arc<PubkeyMatcher>,
+    stream_best: bool,
+    best_score: Arc<AtomicU64>,
+    // Tag solutions with the attempt counter (see --show-attempt)
+    show_attempt: bool,
+    // Re-derive matches with the reference derivation (see --strict-derivation)
+    strict_derivation: bool,
+    solutions: SyncSender<Solution>,
+    backpressure: Backpressure,
+}
+
+fn keys_per_second(attempts: u64, runtime: Duration) -> f64 {
+    let seconds = runtime.as_secs_f64();
+    if seconds > 0.0 {
+        attempts as f64 / seconds
+    } else {
+        0.0
+    }
+}
+
+// Number of attempts after which a match is found with probability `quantile`. Every attempt
+// matches independently with `match_probability`, so the attempts are geometrically distributed.
+fn attempts_quantile(match_probability: f64, quantile: f64) -> f64 {
+    if match_probability >= 1.0 {
+        return 1.0;
+    }
+    ((-quantile).ln_1p() / (-match_probability).ln_1p())
+        .ceil()
+        .max(1.0)
+}
+
+// Keys per second of a single CPU thread, measured for about `duration`
+fn measure_cpu_rate(generate_key_type: GenerateKeyType, duration: Duration) -> Result<f64, Error> {
+    let mut start = [0u8; 32];
+    new_rng(RngSource::Os)?.fill_bytes(&mut start);
+    let start_time = Instant::now();
+    let mut keys = 0;
+    for key_material in KeySpaceWalker::new(start) {
+        hint::black_box(pubkey_to_address(&secret_to_pubkey(
+            key_material,
+            generate_key_type,
+        )));
+        keys += 1;
+        if start_time.elapsed() >= duration {
+            break;
+        }
+    }
+    Ok(keys_per_second(keys, start_time.elapsed()))
+}
+
+// The estimate of --estimate-only --estimate-format json
+// With `lower_bound`, all numbers are only lower bounds (see `unsampled_conditions`)
+fn estimate_json(
+    estimated_attempts: &BigInt,
+    match_probability: f64,
+    keys_per_second: f64,
+    lower_bound: bool,
+) -> String {
+    format!(
+        "{{\"expected_attempts\":{},\"median_attempts\":{:.0},\"p95_attempts\":{:.0},\"estimated_seconds\":{:.1},\"lower_bound\":{}}}",
+        estimated_attempts,
+        attempts_quantile(match_probability, 0.5),
+        attempts_quantile(match_probability, 0.95),
+        1.0 / match_probability / keys_per_second,
+        lower_bound,
+    )
+}
+
+// e.g. "at least 100000" if the estimate is only a lower bound
+fn estimate_text(attempts: String, lower_bound: bool) -> String {
+    if lower_bound {
+        format!("at least {}", attempts)
+    } else {
+        attempts
+    }
+}
+
+// Conditions without a sampled match may be rare or impossible, e.g. "^x"
+fn warn_unsampled_conditions(matcher: &PubkeyMatcher) {
+    for description in matcher.unsampled_conditions() {
+        eprintln!(
+            "Warning: {} did not match any sampled address. The estimate is only a lower bound and the pattern may be impossible.",
+            description
+        );
+    }
+}
+
+// Set by the SIGINT handler of --group-by-difficulty
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn handle_sigint(_signal: libc::c_int) {
+    INTERRUPTED.store(true, atomic::Ordering::Relaxed);
+}
+
+// Turns Ctrl-C into a regular stop, such that the workers exit and the output thread reports
+// the matches found so far
+fn stop_on_interrupt(stop: Arc<AtomicBool>) {
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            handle_sigint as extern "C" fn(libc::c_int) as libc::sighandler_t,
         );
+    }
+    thread::spawn(move || {
+        while !stop.load(atomic::Ordering::Relaxed) {
+            if INTERRUPTED.load(atomic::Ordering::Relaxed) {
+                stop.store(true, atomic::Ordering::Relaxed);
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    });
+}
+
+// Floating point optimizations that are not meant for the integer and hash code of the kernel
+const REJECTED_GPU_BUILD_OPTIONS: [&str; 4] = [
+    "-cl-fast-relaxed-math",
+    "-cl-unsafe-math-optimizations",
+    "-cl-finite-math-only",
+    "-cl-no-signed-zeros",
+];
+
+// Checks the OpenCL compiler options of --gpu-build-opts and joins them with single spaces.
+// Only plain options are accepted, no quotes or shell characters.
+fn parse_gpu_build_options(input: &str) -> Result<String, String> {
+    let mut options = Vec::new();
+    for option in input.split_whitespace() {
+        if !option.starts_with('-') {
+            return Err(format!("\"{}\" is not an option", option));
+        }
+        if let Some(c) = option
+            .chars()
+            .find(|&c| !c.is_ascii_alphanumeric() && !"-_=.,+/".contains(c))
+        {
+            return Err(format!(
+                "\"{}\" contains the unsupported character '{}'",
+                option, c
+            ));
+        }
+        if REJECTED_GPU_BUILD_OPTIONS.contains(&option) {
+            return Err(format!("{} would affect the integer and hash code", option));
+        }
+        options.push(option);
+    }
+    Ok(options.join(" "))
+}
+
+// Opens the CSV file of --rate-log for appending and writes the header to new files
+fn open_rate_log(path: &Path) -> Result<fs::File, Error> {
+    let mut file = fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)?;
+    if file.metadata()?.len() == 0 {
+        writeln!(file, "timestamp,attempts,keys_per_sec")?;
+    }
+    Ok(file)
+}
+
+// The number of CPU threads: `requested` with --cpu-threads (0 for GPU only), otherwise
+// one per core but one, which is left for the GPU and the output. At least one thread is
+// used by default, also on a single core or if the core count is unknown (0).
+fn resolve_thread_count(requested: Option<usize>, cores: usize) -> usize {
+    match requested {
+        Some(threads) => threads,
+        None => cores.saturating_sub(1).max(1),
+    }
+}
+
+// Increments the low `bits` bits of the big endian key, leaving the other bits unchanged.
+// Returns true if the low bits wrapped around to zero.
+fn increment_low_bits(key: &mut [u8; 32], bits: usize) -> bool {
+    let full_bytes = bits / 8;
+    for byte in key.iter_mut().rev().take(full_bytes) {
+        *byte = byte.wrapping_add(1);
+        if *byte != 0 {
+            return false;
+        }
+    }
+    let partial_bits = bits % 8;
+    if partial_bits == 0 {
+        return true;
+    }
+    let index = 31 - full_bytes;
+    let mask = (1u8 << partial_bits) - 1;
+    let low = (key[index] & mask).wrapping_add(1) & mask;
+    key[index] = (key[index] & !mask) | low;
+    low == 0
+}
+
+// Replaces all but the low `bits` bits of the big endian key with random bits
+fn randomize_high_bits(key: &mut [u8; 32], bits: usize, rng: &mut dyn Rng) {
+    let mut random = [0u8; 32];
+    rng.fill_bytes(&mut random);
+    for (i, byte) in key.iter_mut().enumerate() {
+        // number of bits of this byte that belong to the low bits
+        let low_bits = bits.saturating_sub((31 - i) * 8).min(8);
+        let mask = ((1u16 << low_bits) - 1) as u8;
+        *byte = (*byte & mask) | (random[i] & !mask);
+    }
+}
+
+// Draws a random starting key that differs from all `seeds`. Fails if the RNG repeats
+// itself or produces zeros, which indicates a broken RNG rather than bad luck.
+fn new_seed(seeds: &[[u8; 32]], rng: &mut dyn Rng) -> Result<[u8; 32], Error> {
+    for _ in 0..3 {
+        let mut seed = [0u8; 32];
+        rng.fill_bytes(&mut seed);
+        if seed != [0u8; 32] && !seeds.contains(&seed) {
+            return Ok(seed);
+        }
+    }
+    Err(Error::Io(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "The random number generator produced repeated seeds",
+    )))
+}
+
+// Expected energy in kWh to find one match at the given speed and power draw
+fn expected_kwh_per_match(match_probability: f64, keys_per_second: f64, watts: f64) -> Option<f64> {
+    if keys_per_second > 0.0 && match_probability > 0.0 {
+        let seconds = 1.0 / match_probability / keys_per_second;
+        Some(watts * seconds / 3600.0 / 1000.0)
     } else {
-        match secret_key_type {
-            GenerateKeyType::LiskPassphrase => println!(
-                "Found matching account!\nPrivate Key: {}\nAddress:     {}",
-                String::from_utf8(entropy_to_mnemonic(cut_last_16(&secret_key_material))).unwrap(),
-                full_address(pubkey_to_address(&public_key)),
-            ),
-            GenerateKeyType::PrivateKey => println!(
-                "Found matching account!\nPrivate Key: {}{}\nAddress:     {}",
-                hex::encode_upper(&secret_key_material as &[u8]),
-                hex::encode_upper(&public_key),
-                full_address(pubkey_to_address(&public_key)),
-            ),
+        None
+    }
+}
+
+// Counts `n` attempts before they are made. Returns false and stops all workers if the
+// attempt budget is exhausted. Batches of n > 1 may overshoot the budget by up to n - 1.
+fn claim_attempts(params: &ThreadParams, n: u64) -> bool {
+    if !params.count_attempts {
+        return true;
+    }
+    match params.max_attempts {
+        Some(max_attempts) => {
+            let claimed = params.attempts.fetch_update(
+                atomic::Ordering::Relaxed,
+                atomic::Ordering::Relaxed,
+                |attempts| {
+                    if attempts < max_attempts {
+                        Some(attempts + n)
+                    } else {
+                        None
+                    }
+                },
+            );
+            if claimed.is_err() {
+                params.stop.store(true, atomic::Ordering::Relaxed);
+            }
+            claimed.is_ok()
+        }
+        None => {
+            params.attempts.fetch_add(n, atomic::Ordering::Relaxed);
+            true
         }
     }
 }
 
-struct ThreadParams {
-    limit: usize,
-    found_n: Arc<AtomicUsize>,
-    output_progress: bool,
-    attempts: Arc<AtomicUsize>,
-    simple_output: bool,
-    generate_key_type: GenerateKeyType,
-    matcher: Arc<PubkeyMatcher>,
+// Sleeps while the search is paused. Returns false if the workers should exit.
+fn wait_while_paused(params: &ThreadParams) -> bool {
+    while params.paused.load(atomic::Ordering::Relaxed)
+        && !params.stop.load(atomic::Ordering::Relaxed)
+    {
+        thread::sleep(Duration::from_millis(100));
+    }
+    !params.stop.load(atomic::Ordering::Relaxed)
 }
 
-fn full_address(address: u64) -> String {
-    return format!("{}L", address);
+// Lowers the shared best score to `score`. Returns true if this was an improvement.
+fn improve_best_score(best_score: &AtomicU64, score: u64) -> bool {
+    let mut best = best_score.load(atomic::Ordering::Relaxed);
+    while score < best {
+        match best_score.compare_exchange_weak(
+            best,
+            score,
+            atomic::Ordering::Relaxed,
+            atomic::Ordering::Relaxed,
+        ) {
+            Ok(_) => return true,
+            Err(current) => best = current,
+        }
+    }
+    false
 }
 
 fn check_solution(params: &ThreadParams, key_material: [u8; 32]) -> bool {
     let public_key = secret_to_pubkey(key_material, params.generate_key_type);
-    let matches = params.matcher.matches(&public_key);
-    if matches {
-        if params.output_progress {
-            eprintln!("");
+    check_public_key(params, key_material, public_key)
+}
+
+// Reports a key the GPU found that does not match on the CPU. The key material is only
+// shown if secrets may be output (see --output-pubkey-only).
+fn non_matching_solution_message(
+    key_material: [u8; 32],
+    generate_key_type: GenerateKeyType,
+    pubkey_only: bool,
+) -> String {
+    if pubkey_only {
+        format!(
+            "GPU returned non-matching solution with public key {}",
+            hex::encode_upper(secret_to_pubkey(key_material, generate_key_type))
+        )
+    } else {
+        format!(
+            "GPU returned non-matching solution: {}",
+            hex::encode_upper(key_material)
+        )
+    }
+}
+
+// Like `check_solution` for an already derived public key
+fn check_public_key(params: &ThreadParams, key_material: [u8; 32], public_key: [u8; 32]) -> bool {
+    let address = pubkey_to_address(&public_key);
+    let matches = params.matcher.matches_address(address);
+    if matches && params.strict_derivation {
+        if let Err(message) = check_derivation(key_material, params.generate_key_type, public_key) {
+            exit_with_error(&Error::DerivationMismatch(message));
         }
-        print_solution(
+    }
+    let improved = params.stream_best
+        && improve_best_score(&params.best_score, params.matcher.score_address(address));
+    let kind = if matches {
+        Some(SolutionKind::Match)
+    } else if improved {
+        Some(SolutionKind::BetterCandidate)
+    } else {
+        None
+    };
+    if let Some(kind) = kind {
+        let solution = Solution {
+            kind,
             key_material,
-            params.generate_key_type,
+            key_type: params.generate_key_type,
             public_key,
-            params.simple_output,
-        );
-        if params.limit != 0
-            && params.found_n.fetch_add(1, atomic::Ordering::Relaxed) + 1 >= params.limit
-        {
-            process::exit(0);
-        }
+            attempt: if params.show_attempt {
+                Some(params.attempts.load(atomic::Ordering::Relaxed))
+            } else {
+                None
+            },
+        };
+        send_solution(&params.solutions, params.backpressure, solution);
     }
     matches
 }
 
+// The key type selected on the command line. Passphrases are the default, which
+// --generate-passphrase selects explicitly.
+fn generate_key_type(args: &clap::ArgMatches) -> GenerateKeyType {
+    if args.is_present("generate_keypair") {
+        GenerateKeyType::PrivateKey
+    } else {
+        GenerateKeyType::LiskPassphrase
+    }
+}
+
+// Re-derives the addresses of "--simple-output" lines and returns the process exit code
+fn reproduce_command(args: &clap::ArgMatches) -> Result<i32, Error> {
+    let generate_key_type = generate_key_type(args);
+
+    let lines: Vec<String> = match args.value_of("line") {
+        Some(line) => vec![line.to_string()],
+        None => io::stdin()
+            .lock()
+            .lines()
+            .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+            .collect::<Result<_, _>>()?,
+    };
+
+    let mut exit_code = 0;
+    for line in lines {
+        match parse_simple_output_line(&line) {
+            Ok((key_material, address)) => {
+                match reproduce(key_material, address, generate_key_type) {
+                    Ok(()) => println!("Match:    {}", full_address(address)),
+                    Err(derived) => {
+                        println!(
+                            "Mismatch: {} (derived {})",
+                            full_address(address),
+                            full_address(derived)
+                        );
+                        exit_code = 1;
+                    }
+                }
+            }
+            Err(error) => {
+                eprintln!("{}", error);
+                exit_code = 1;
+            }
+        }
+    }
+    Ok(exit_code)
+}
+
+// Derives the address of a passphrase from another tool with the same code as the search
+fn address_command(args: &clap::ArgMatches) -> Result<i32, Error> {
+    let passphrase = match args.value_of("passphrase") {
+        Some(passphrase) => passphrase.to_string(),
+        None => {
+            let mut line = String::new();
+            io::stdin().read_line(&mut line)?;
+            line.trim_end_matches(&['\r', '\n'][..]).to_string()
+        }
+    };
+    let key_material = mnemonic_to_key_material(&passphrase)
+        .map_err(|error| Error::Argument(format!("Invalid passphrase: {}", error)))?;
+    let public_key = secret_to_pubkey(key_material, GenerateKeyType::LiskPassphrase);
+    let address = pubkey_to_address(&public_key);
+    match args.value_of("format").unwrap() {
+        "numeric" => println!("{}", address),
+        "display" => println!("{}", full_address(address)),
+        "pubkey" => println!("{}", hex::encode_upper(public_key)),
+        _ => {
+            println!("Address:     {}", full_address(address));
+            println!("Numeric:     {}", address);
+            println!("Public Key:  {}", hex::encode_upper(public_key));
+        }
+    }
+    Ok(0)
+}
+
+// Decodes a recipe and prints its address if the key material derives to it
+fn verify_recipe_command(args: &clap::ArgMatches) -> Result<i32, Error> {
+    let recipe = Recipe::decode(args.value_of("recipe").unwrap())
+        .map_err(|error| Error::Argument(format!("Invalid recipe: {}", error)))?;
+    match recipe.verify() {
+        Ok(address) => {
+            println!("Match:    {}", full_address(address));
+            Ok(0)
+        }
+        Err(derived) => {
+            println!(
+                "Mismatch: {} (derived {})",
+                full_address(recipe.address),
+                full_address(derived)
+            );
+            Ok(1)
+        }
+    }
+}
+
+// Times `encode` over all `pubkeys`
+fn time_encoder<T, F: Fn(&[u8; 32]) -> T>(pubkeys: &[[u8; 32]], encode: F) -> Duration {
+    let start = Instant::now();
+    for pubkey in pubkeys {
+        hint::black_box(encode(hint::black_box(pubkey)));
+    }
+    start.elapsed()
+}
+
+// Reports the throughput of the address encodings, without key derivation and matching
+fn bench_encode_command(args: &clap::ArgMatches) -> Result<i32, Error> {
+    let count: usize = parse_value(args.value_of("count").unwrap(), "COUNT")?;
+    if count == 0 {
+        return Err(Error::Argument("COUNT must be at least 1".to_string()));
+    }
+    // Any 32 bytes are hashed the same way as a valid public key
+    let mut rng = new_rng(RngSource::Os)?;
+    let mut pubkeys = vec![[0u8; 32]; count];
+    for pubkey in &mut pubkeys {
+        rng.fill_bytes(pubkey);
+    }
+
+    let numeric = time_encoder(&pubkeys, pubkey_to_address);
+    let display = time_encoder(&pubkeys, |pubkey| full_address(pubkey_to_address(pubkey)));
+    // The default search compares the numeric address to 10^LENGTH - 1, without digits
+    let matcher = PubkeyMatcher::new(12);
+    let length_match = time_encoder(&pubkeys, |pubkey| matcher.matches(pubkey));
+    println!(
+        "Encoded {} random public keys",
+        group_thousands(&count.to_string())
+    );
+    for &(format, runtime) in &[
+        ("numeric", numeric),
+        ("display", display),
+        ("length match", length_match),
+    ] {
+        println!(
+            "{:<12} {:>14} encodings/s",
+            format,
+            group_thousands(&(keys_per_second(count as u64, runtime) as u64).to_string())
+        );
+    }
+    Ok(0)
+}
+
+// Description of this build for wrapping tools (see --capabilities)
+fn capabilities_json() -> String {
+    let array = |values: &[&str]| {
+        let quoted: Vec<String> = values
+            .iter()
+            .map(|value| format!("\"{}\"", value))
+            .collect();
+        format!("[{}]", quoted.join(","))
+    };
+    let key_types: Vec<&str> = KEY_TYPES.iter().map(|key_type| key_type.name()).collect();
+    format!(
+        "{{\"version\":\"{}\",\"gpu\":{},\"formats\":{},\"matchers\":{},\"keyTypes\":{},\"hashes\":{}}}",
+        env!("CARGO_PKG_VERSION"),
+        cfg!(feature = "gpu"),
+        array(OUTPUT_FORMATS),
+        array(CONDITION_KEYWORDS),
+        array(&key_types),
+        // addresses are the first 8 bytes of SHA-256(public key)
+        array(&["sha256"]),
+    )
+}
+
+// Asks the user on stderr whether private keys may be printed to the terminal
+fn confirm_secrets() -> Result<bool, Error> {
+    ask("This will print private keys to the terminal. Continue?")
+}
+
+// Asks a yes/no question on stderr. Everything but "y" means no.
+fn ask(question: &str) -> Result<bool, Error> {
+    eprint!("{} [y/N] ", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(answer == "y" || answer == "Y")
+}
+
+// Prints the addresses of `count` matches of the relaxed matcher and asks whether to
+// continue with the real target
+fn preview(
+    matcher: &PubkeyMatcher,
+    generate_key_type: GenerateKeyType,
+    rng_source: RngSource,
+    count: usize,
+) -> Result<bool, Error> {
+    let relaxed = matcher.relaxed();
+    eprintln!(
+        "Searching {} preview addresses of the relaxed pattern (estimated attempts per address: {})",
+        count,
+        relaxed.estimated_attempts()
+    );
+    let mut rng = new_rng(rng_source)?;
+    let mut start = [0u8; 32];
+    rng.fill_bytes(&mut start);
+    let addresses = KeySpaceWalker::new(start)
+        .map(|key_material| pubkey_to_address(&secret_to_pubkey(key_material, generate_key_type)))
+        .filter(|&address| relaxed.matches_address(address))
+        .take(count);
+    for address in addresses {
+        println!("Preview: {}", full_address(address));
+    }
+    ask("Proceed with the real target?")
+}
+
 fn main() {
+    if let Err(error) = run() {
+        exit_with_error(&error);
+    }
+}
+
+fn run() -> Result<(), Error> {
     let args = clap::App::new("lisk-vanity")
         .version(env!("CARGO_PKG_VERSION"))
         .author("Simon Warta <simon@warta.it>")
@@ -137,18 +707,178 @@ fn main() {
                 .required_unless("suffix")
                 .help("The max length for the address"),
         )
+        .arg(
+            clap::Arg::with_name("pattern")
+                .long("pattern")
+                .value_name("PATTERN")
+                .help("Search for addresses matching a pattern like \"len<=12\", \"prefix:100\", \"suffix:777\" or \"contains:8888\", combined with \"&\" and \"|\". An explicit LENGTH is combined with the pattern using \"&\"."),
+        )
+        .arg(
+            clap::Arg::with_name("patterns_file")
+                .long("patterns-file")
+                .value_name("FILE")
+                .help("Search for addresses matching any of the patterns in FILE, one per line, in a single pass. Empty lines and lines starting with \"#\" are ignored. Matches show the lines they satisfy. Combined like --pattern."),
+        )
+        .arg(
+            clap::Arg::with_name("like")
+                .long("like")
+                .value_name("ADDRESS")
+                .help("Search for addresses similar to ADDRESS, sharing its first and last digits. Replaces the default LENGTH, but an explicit LENGTH or pattern is combined using \"&\"."),
+        )
+        .arg(
+            clap::Arg::with_name("prefix")
+                .long("prefix")
+                .value_name("DIGITS")
+                .help("Only match addresses starting with DIGITS, e.g. \"1234\", in the --base if given. Replaces the default LENGTH, but an explicit LENGTH is combined. Not supported on the GPU."),
+        )
+        .arg(
+            clap::Arg::with_name("contains")
+                .long("contains")
+                .value_name("DIGITS")
+                .help("Only match addresses containing DIGITS anywhere, e.g. \"420\", in the --base if given. Replaces the default LENGTH, but an explicit LENGTH is combined."),
+        )
+        .arg(
+            clap::Arg::with_name("regex")
+                .long("regex")
+                .value_name("REGEX")
+                .help("Only match addresses with the \"L\" suffix matching the regular expression, e.g. \"^1\\d{3}L$\". Supports literals, \".\", \"[...]\", \"\\d\", \"^\", \"$\", \"(...|...)\" and the quantifiers \"*+?{n,m}\". Replaces the default LENGTH, but an explicit LENGTH is combined. CPU only; the estimate is sampled."),
+        )
+        .arg(
+            clap::Arg::with_name("suffix")
+                .long("suffix")
+                .value_name("DIGITS")
+                .help("Only match addresses ending in DIGITS, e.g. \"777\" or \"777L\", in the --base if given. Replaces the default LENGTH, but an explicit LENGTH is combined."),
+        )
+        .arg(
+            clap::Arg::with_name("lucky")
+                .long("lucky")
+                .value_name("LIST")
+                .help("Only match addresses containing at least one of the comma separated digits, e.g. \"8,88,888\". Replaces the default LENGTH."),
+        )
+        .arg(
+            clap::Arg::with_name("unlucky")
+                .long("unlucky")
+                .value_name("LIST")
+                .help("Only match addresses containing none of the comma separated digits, e.g. \"4\". Lucky numbers containing an unlucky one are ignored."),
+        )
+        .arg(
+            clap::Arg::with_name("base")
+                .long("base")
+                .value_name("N")
+                .help("Apply the prefix, suffix and contains conditions of the pattern or the patterns file, --prefix, --suffix and --contains to the address in base N (2 to 36, letters in lowercase). All other conditions use the decimal address."),
+        )
+        .arg(
+            clap::Arg::with_name("display_base")
+                .long("display-base")
+                .requires("base")
+                .help("Also show the address in the --base in the human readable output"),
+        )
+        .arg(
+            clap::Arg::with_name("uri")
+                .long("uri")
+                .help("Also output the address as a \"lisk:\" wallet link, e.g. \"lisk:1234L\". Appended as the last column of --simple-output."),
+        )
+        .arg(
+            clap::Arg::with_name("like_prefix_len")
+                .long("like-prefix-len")
+                .value_name("N")
+                .requires("like")
+                .help("The number of leading digits shared with the --like address [default: 6]"),
+        )
+        .arg(
+            clap::Arg::with_name("like_suffix_len")
+                .long("like-suffix-len")
+                .value_name("N")
+                .requires("like")
+                .help("The number of trailing digits shared with the --like address [default: 4]"),
+        )
+        .arg(
+            clap::Arg::with_name("min_length")
+                .long("min-length")
+                .value_name("N")
+                .help("Only match addresses with at least N digits. Together with LENGTH this gives a band of lengths."),
+        )
+        .arg(
+            clap::Arg::with_name("exact_length")
+                .long("exact-length")
+                .value_name("K")
+                .help("Only match addresses with exactly K digits. Replaces the default LENGTH, but an explicit LENGTH or pattern is combined using \"&\"."),
+        )
+        .arg(
+            clap::Arg::with_name("target_length")
+                .long("target-length")
+                .value_name("N")
+                .help("Only match addresses with N digits, give or take --length-tolerance. Replaces the default LENGTH like --exact-length."),
+        )
+        .arg(
+            clap::Arg::with_name("length_tolerance")
+                .long("length-tolerance")
+                .alias("max-digits-diff")
+                .value_name("M")
+                .requires("target_length")
+                .help("The number of digits the address length may differ from --target-length [default: 0]"),
+        )
+        .arg(
+            clap::Arg::with_name("digit_count")
+                .long("digit-count")
+                .value_name("RULES")
+                .help("Only match addresses with the given digit counts, e.g. \"7>=3,0<=1\" for at least three 7s and at most one 0. Combined with LENGTH or the pattern using \"&\"."),
+        )
+        .arg(
+            clap::Arg::with_name("luhn")
+                .long("luhn")
+                .help("Only match addresses whose digits pass the Luhn checksum. Combined with LENGTH or the pattern using \"&\"."),
+        )
+        .arg(
+            clap::Arg::with_name("mirror")
+                .long("mirror")
+                .help("Only match addresses with an odd number of digits that read the same in reverse order, e.g. \"12321\". The center digit is free."),
+        )
+        .arg(
+            clap::Arg::with_name("hash_leading_zeros")
+                .long("hash-leading-zeros")
+                .value_name("N")
+                .help("Only match public keys whose SHA-256 digest starts with N zero bits (1 to 64), independent of the rendered address. Takes about 2^N attempts."),
+        )
+        .arg(
+            clap::Arg::with_name("grouped_pattern")
+                .long("grouped-pattern")
+                .value_name("GROUPING")
+                .possible_values(&["equal", "palindrome"])
+                .help("Only match addresses whose groups of three digits are all equal (\"123,123,123\") or read the same in reverse order (\"123,456,123\")"),
+        )
+        .arg(
+            clap::Arg::with_name("monotonic")
+                .long("monotonic")
+                .value_name("DIRECTION")
+                .possible_values(&["inc", "dec"])
+                .help("Only match addresses whose digits are strictly increasing (\"1357\") or decreasing (\"975\")"),
+        )
         .arg(
             clap::Arg::with_name("generate_keypair")
                 .short("k")
                 .long("generate-keypair")
                 .help("Generate a key pair instead of a passphrase"),
         )
+        .arg(
+            clap::Arg::with_name("generate_passphrase")
+                .long("generate-passphrase")
+                .conflicts_with("generate_keypair")
+                .help("Generate a passphrase (the default)"),
+        )
         .arg(
             clap::Arg::with_name("cpu_threads")
                 .short("t")
                 .long("cpu-threads")
                 .value_name("N")
-                .help("The number of CPU threads to use [default: number of cores minus one]"),
+                .help("The number of CPU threads to use [default: number of cores minus one, at least one]"),
+        )
+        .arg(
+            clap::Arg::with_name("cpu_batch")
+                .long("cpu-batch")
+                .value_name("N")
+                .default_value("1")
+                .help("The number of keys each CPU thread derives before matching them"),
         )
         .arg(
             clap::Arg::with_name("gpu")
@@ -165,196 +895,1662 @@ fn main() {
                 .help("Generate N addresses, then exit (0 for infinite)"),
         )
         .arg(
-            clap::Arg::with_name("gpu_threads")
-                .long("gpu-threads")
+            clap::Arg::with_name("max_attempts")
+                .long("max-attempts")
+                .alias("budget")
                 .value_name("N")
-                .default_value("1048576")
-                .help("The number of GPU threads to use"),
+                .help("Stop after trying N keys in total, e.g. for bounded test runs. The GPU may exceed N by up to one batch."),
         )
         .arg(
-            clap::Arg::with_name("gpu_local_work_size")
-                .long("gpu-local-work-size")
+            clap::Arg::with_name("summary_every")
+                .long("summary-every")
                 .value_name("N")
-                .help("The GPU local work size. A custom value it may increase performance. By default the OpenCL driver is responsible for setting a proper value. Don't use this if you don't know what you are doing."),
+                .help("Print the number of matches, their average difficulty, the shortest address and the match rate after every N matches"),
         )
         .arg(
-            clap::Arg::with_name("no_progress")
-                .long("no-progress")
-                .help("Disable progress output"),
+            clap::Arg::with_name("grace")
+                .long("grace")
+                .value_name("DURATION")
+                .help("After reaching the limit, keep searching for this long (e.g. \"30s\", \"5m\") and print the shortest match found at the end"),
         )
         .arg(
-            clap::Arg::with_name("simple_output")
-                .long("simple-output")
-                .help("Output found keys in the form \"[key] [address]\""),
+            clap::Arg::with_name("on_backpressure")
+                .long("on-backpressure")
+                .value_name("MODE")
+                .possible_values(&["block", "drop"])
+                .default_value("block")
+                .help("What to do with new solutions when the output cannot keep up: wait for the output or drop them"),
         )
         .arg(
-            clap::Arg::with_name("gpu_platform")
-                .long("gpu-platform")
-                .value_name("INDEX")
-                .default_value("0")
-                .help("The GPU platform to use"),
+            clap::Arg::with_name("seed_increment_bits")
+                .long("seed-increment-bits")
+                .value_name("N")
+                .default_value("256")
+                .help("CPU workers only increment the low N bits of the random key and re-randomize the other bits when these wrap around"),
         )
         .arg(
-            clap::Arg::with_name("gpu_device")
-                .long("gpu-device")
-                .value_name("INDEX")
-                .default_value("0")
-                .help("The GPU device to use"),
+            clap::Arg::with_name("record")
+                .long("record")
+                .value_name("FILE")
+                .conflicts_with("replay")
+                .help("Write the start keys and generator seeds of the CPU threads to FILE, such that --replay can repeat the search"),
         )
-        .get_matches();
-
-    let max_length = args
-        .value_of("length")
-        .unwrap()
-        .parse()
-        .expect("Failed to parse LENGTH");
+        .arg(
+            clap::Arg::with_name("replay")
+                .long("replay")
+                .value_name("FILE")
+                .conflicts_with_all(&["checkpoint", "gpu", "cpu_threads", "test_inject_key"])
+                .help("Repeat the key sequence of the CPU threads recorded with --record. Use the same pattern options as the recorded run."),
+        )
+        .arg(
+            clap::Arg::with_name("checkpoint")
+                .long("checkpoint")
+                .value_name("FILE")
+                .help("Periodically save the current key of every CPU thread to FILE and continue from the saved keys when FILE exists on startup"),
+        )
+        .arg(
+            clap::Arg::with_name("checkpoint_interval")
+                .long("checkpoint-interval")
+                .value_name("DURATION")
+                .requires("checkpoint")
+                .help("How often to save the checkpoint [default: 60s]"),
+        )
+        .arg(
+            clap::Arg::with_name("state_file")
+                .long("state-file")
+                .value_name("FILE")
+                .help("Keep the total attempts and search time of all sessions with the same pattern in FILE. The progress and summary include the earlier sessions. A missing or invalid FILE starts from zero."),
+        )
+        .arg(
+            clap::Arg::with_name("events_fd")
+                .long("events-fd")
+                .value_name("FD")
+                .help("Write lifecycle events (started, estimate, gpu_ready, progress, found, stopped) as JSON lines to the open file descriptor FD. Private keys are never included."),
+        )
+        .arg(
+            clap::Arg::with_name("rate_log")
+                .long("rate-log")
+                .value_name("FILE")
+                .help("Append \"timestamp,attempts,keys_per_sec\" lines to the CSV file FILE, e.g. to spot thermal throttling in long runs. The rate is measured over the last interval."),
+        )
+        .arg(
+            clap::Arg::with_name("rate_log_interval")
+                .long("rate-log-interval")
+                .value_name("DURATION")
+                .requires("rate_log")
+                .help("How often to append to the rate log [default: 10s]"),
+        )
+        .arg(
+            clap::Arg::with_name("rng")
+                .long("rng")
+                .value_name("RNG")
+                .possible_values(&["os", "chacha20"])
+                .default_value("os")
+                .help("The generator for random keys: the operating system's or ChaCha20 seeded from it"),
+        )
+        .arg(
+            clap::Arg::with_name("gpu_threads")
+                .long("gpu-threads")
+                .value_name("N")
+                .default_value("1048576")
+                .help("The number of GPU threads to use"),
+        )
+        .arg(
+            clap::Arg::with_name("gpu_auto_batch")
+                .long("gpu-auto-batch")
+                .help("Tune the number of GPU threads over the first launches such that each launch takes about 100ms, starting from --gpu-threads. Keeps the progress and pausing responsive on any GPU."),
+        )
+        .arg(
+            clap::Arg::with_name("gpu_local_work_size")
+                .long("gpu-local-work-size")
+                .value_name("N")
+                .help("The GPU local work size. A custom value it may increase performance. By default the OpenCL driver is responsible for setting a proper value. Don't use this if you don't know what you are doing."),
+        )
+        .arg(
+            clap::Arg::with_name("gpu_build_opts")
+                .long("gpu-build-opts")
+                .value_name("FLAGS")
+                .allow_hyphen_values(true)
+                .help("Options for building the GPU kernel, e.g. \"-cl-std=CL1.2\", to work around driver quirks. Options that relax floating point math are rejected. [default: none]"),
+        )
+        .arg(
+            clap::Arg::with_name("no_progress")
+                .long("no-progress")
+                .help("Disable progress output"),
+        )
+        .arg(
+            clap::Arg::with_name("watts")
+                .long("watts")
+                .value_name("N")
+                .help("Power draw of the machine in watts. Adds the expected energy per match to the progress output."),
+        )
+        .arg(
+            clap::Arg::with_name("cost_per_kwh")
+                .long("cost-per-kwh")
+                .value_name("X")
+                .requires("watts")
+                .help("Electricity price per kWh. Adds the expected cost per match to the progress output."),
+        )
+        .arg(
+            clap::Arg::with_name("target")
+                .long("target")
+                .value_name("ADDRESS")
+                .requires("max_attempts")
+                .help("Search for ADDRESS and report the address sharing the most leading and trailing digits with it when the --max-attempts budget is exhausted"),
+        )
+        .arg(
+            clap::Arg::with_name("sort_output")
+                .long("sort-output")
+                .help("Output all matches sorted by address once the limit is reached instead of as they are found"),
+        )
+        .arg(
+            clap::Arg::with_name("stream_best")
+                .long("stream-best")
+                .help("Also output every address that is better (shorter) than all addresses seen before, giving a stream of ever-better candidates"),
+        )
+        .arg(
+            clap::Arg::with_name("human_numbers")
+                .long("human-numbers")
+                .help("Use thousands separators for large numbers in the estimate and progress output"),
+        )
+        .arg(
+            clap::Arg::with_name("simple_output")
+                .long("simple-output")
+                .help("Output found keys in the form \"[key] [address]\""),
+        )
+        .arg(
+            clap::Arg::with_name("show_pubkey")
+                .long("show-pubkey")
+                .help("Include the public key in the output"),
+        )
+        .arg(
+            clap::Arg::with_name("output_dir")
+                .long("output-dir")
+                .value_name("DIR")
+                .help("Also write every match to its own file \"[address]L.json\" in DIR, which is created if missing"),
+        )
+        .arg(
+            clap::Arg::with_name("socket")
+                .long("socket")
+                .value_name("PATH")
+                .help("Connect to the Unix domain socket PATH and send matches to it as JSON lines instead of printing them"),
+        )
+        .arg(
+            clap::Arg::with_name("json_schema")
+                .long("json-schema")
+                .help("Print the JSON schema of the files written by --output-dir and exit"),
+        )
+        .arg(
+            clap::Arg::with_name("capabilities")
+                .long("capabilities")
+                .help("Print the version, GPU support, output formats, pattern conditions, key types and address hashes of this build as JSON and exit"),
+        )
+        .arg(
+            clap::Arg::with_name("force")
+                .long("force")
+                .requires("output_dir")
+                .help("Overwrite existing files in the output directory"),
+        )
+        .arg(
+            clap::Arg::with_name("preview")
+                .long("preview")
+                .value_name("K")
+                .help("Show K addresses matching a relaxed version of the pattern before searching"),
+        )
+        .arg(
+            clap::Arg::with_name("recipe")
+                .long("recipe")
+                .help("Include a recipe in the output that lets \"verify-recipe\" re-derive the address"),
+        )
+        .arg(
+            clap::Arg::with_name("output_pubkey_only")
+                .long("output-pubkey-only")
+                .conflicts_with_all(&["simple_output", "recipe", "output_dir", "socket", "checkpoint", "record", "debug_derivation"])
+                .help("Output only \"[public key],[address]\" lines and never any secret, e.g. on an untrusted machine. The private keys are discarded."),
+        )
+        .arg(
+            clap::Arg::with_name("show_entropy")
+                .long("show-entropy")
+                .help("Include the entropy of the private key in bits in the output"),
+        )
+        .arg(
+            clap::Arg::with_name("strict_derivation")
+                .long("strict-derivation")
+                .help("Re-derive every match with a slow reference implementation of the passphrase and address encoding and abort if the results differ"),
+        )
+        .arg(
+            clap::Arg::with_name("show_attempt")
+                .long("show-attempt")
+                .help("Include the approximate number of attempts before each solution in the output"),
+        )
+        .arg(
+            clap::Arg::with_name("confirm_secrets")
+                .long("confirm-secrets")
+                .help("Ask for confirmation before starting a search that prints private keys to a terminal"),
+        )
+        .arg(
+            clap::Arg::with_name("estimate_only")
+                .long("estimate-only")
+                .conflicts_with_all(&["replay", "test_inject_key"])
+                .help("Measure the CPU speed for a second, print the expected, median and 95th percentile attempts and the expected time, and exit without searching. The GPU is not included."),
+        )
+        .arg(
+            clap::Arg::with_name("estimate_format")
+                .long("estimate-format")
+                .value_name("FORMAT")
+                .possible_values(&["human", "json"])
+                .requires("estimate_only")
+                .help("The format of --estimate-only [default: human]"),
+        )
+        .arg(
+            clap::Arg::with_name("group_by_difficulty")
+                .long("group-by-difficulty")
+                .help("At the end of the search, list the addresses of all matches grouped by their number of digits. Ctrl-C ends the search with this report."),
+        )
+        .arg(
+            clap::Arg::with_name("no_found_banner")
+                .long("no-found-banner")
+                .help("Do not print \"Found matching account!\" before the human readable output of a match. Matches are still counted towards --limit."),
+        )
+        .arg(
+            clap::Arg::with_name("i_understand_swap_risk")
+                .long("i-understand-swap-risk")
+                .help("Search even though swap is active and private keys may be written to disk"),
+        )
+        .arg(
+            clap::Arg::with_name("gpu_platform")
+                .long("gpu-platform")
+                .value_name("INDEX")
+                .default_value("0")
+                .help("The GPU platform to use"),
+        )
+        .arg(
+            clap::Arg::with_name("gpu_device")
+                .long("gpu-device")
+                .value_name("INDEX")
+                .default_value("0")
+                .help("The GPU device to use"),
+        )
+        .arg(
+            clap::Arg::with_name("debug_derivation")
+                .long("debug-derivation")
+                .value_name("KEY")
+                .min_values(0)
+                .max_values(1)
+                .require_equals(true)
+                .help("Print every intermediate value of the derivation of a random key, or of KEY (64 hex characters) with --debug-derivation=KEY, and exit. This prints the private key."),
+        )
+        .arg(
+            clap::Arg::with_name("test_inject_key")
+                .long("test-inject-key")
+                .value_name("HEX")
+                .hidden(true)
+                .help("Testing only: run a single CPU worker starting at the given key with a matcher that accepts every address"),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("reproduce")
+                .about("Re-derive the address of a \"--simple-output\" line and check that it matches")
+                .arg(
+                    clap::Arg::with_name("line")
+                        .long("line")
+                        .value_name("LINE")
+                        .help("The \"[key] [address]\" line to check [default: read lines from stdin]"),
+                )
+                .arg(
+                    clap::Arg::with_name("generate_keypair")
+                        .short("k")
+                        .long("generate-keypair")
+                        .help("The key is a key pair seed instead of a passphrase"),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("bench-encode")
+                .about("Measure the throughput of the address encoding alone, without key derivation and matching")
+                .arg(
+                    clap::Arg::with_name("count")
+                        .long("count")
+                        .value_name("COUNT")
+                        .default_value("1000000")
+                        .help("The number of random public keys to encode"),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("address")
+                .about("Derive the address of an existing passphrase, e.g. to check compatibility with other tools")
+                .arg(
+                    clap::Arg::with_name("passphrase")
+                        .long("passphrase")
+                        .value_name("WORDS")
+                        .help("The 12 word BIP39 passphrase. Note that arguments may end up in the shell history. [default: read a line from stdin]"),
+                )
+                .arg(
+                    clap::Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .possible_values(&["numeric", "display", "pubkey", "all"])
+                        .default_value("display")
+                        .help("Print the numeric address, the address with \"L\" suffix, the public key or all of them"),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("verify-recipe")
+                .about("Decode a recipe printed with \"--recipe\" and check its address")
+                .arg(
+                    clap::Arg::with_name("recipe")
+                        .value_name("RECIPE")
+                        .required(true)
+                        .help("The base64 recipe"),
+                ),
+        )
+        .get_matches();
+
+    if let Some(reproduce_args) = args.subcommand_matches("reproduce") {
+        process::exit(reproduce_command(reproduce_args)?);
+    }
+    if let Some(address_args) = args.subcommand_matches("address") {
+        process::exit(address_command(address_args)?);
+    }
+    if let Some(recipe_args) = args.subcommand_matches("verify-recipe") {
+        process::exit(verify_recipe_command(recipe_args)?);
+    }
+    if let Some(bench_args) = args.subcommand_matches("bench-encode") {
+        process::exit(bench_encode_command(bench_args)?);
+    }
+    if args.is_present("json_schema") {
+        println!("{}", SOLUTION_JSON_SCHEMA);
+        return Ok(());
+    }
+    if args.is_present("capabilities") {
+        println!("{}", capabilities_json());
+        return Ok(());
+    }
+    if args.is_present("debug_derivation") {
+        let mut key_material = [0u8; 32];
+        match args.value_of("debug_derivation") {
+            Some(s) => hex::decode_to_slice(s, &mut key_material).map_err(|_| {
+                Error::Argument(format!("Invalid value \"{}\" for --debug-derivation", s))
+            })?,
+            None => new_rng(RngSource::Os)?.fill_bytes(&mut key_material),
+        }
+        for (label, value) in derivation_trace(key_material, generate_key_type(&args)) {
+            println!("{:<22} {}", format!("{}:", label), value);
+        }
+        return Ok(());
+    }
+
+    let max_length = parse_value(args.value_of("length").unwrap(), "LENGTH")?;
+    if max_length == 0 {
+        return Err(Error::Argument("LENGTH must be at least 1".to_string()));
+    }
 
-    let matcher_base = PubkeyMatcher::new(max_length);
+    // Injected keys always use the test matcher, such that they cannot be mistaken for real finds
+    let injected_key = match args.value_of("test_inject_key") {
+        Some(s) => {
+            let mut key = [0u8; 32];
+            hex::decode_to_slice(s, &mut key).map_err(|_| {
+                Error::Argument(format!("Invalid value \"{}\" for --test-inject-key", s))
+            })?;
+            Some(key)
+        }
+        None => None,
+    };
+    if injected_key.is_some()
+        && (args.occurrences_of("length") != 0
+            || args.is_present("pattern")
+            || args.is_present("patterns_file")
+            || args.is_present("exact_length")
+            || args.is_present("target_length")
+            || args.is_present("min_length")
+            || args.is_present("like")
+            || args.is_present("target")
+            || args.is_present("digit_count")
+            || args.is_present("luhn")
+            || args.is_present("grouped_pattern")
+            || args.is_present("monotonic")
+            || args.is_present("mirror")
+            || args.is_present("hash_leading_zeros")
+            || args.is_present("prefix")
+            || args.is_present("suffix")
+            || args.is_present("contains")
+            || args.is_present("regex")
+            || args.is_present("lucky")
+            || args.is_present("unlucky")
+            || args.is_present("checkpoint"))
+    {
+        return Err(Error::Argument(
+            "--test-inject-key cannot be combined with a search pattern".to_string(),
+        ));
+    }
+
+    let base: u32 = match args.value_of("base") {
+        Some(s) => parse_value(s, "--base")?,
+        None => 10,
+    };
+    if args.is_present("base")
+        && !args.is_present("pattern")
+        && !args.is_present("patterns_file")
+        && !args.is_present("prefix")
+        && !args.is_present("suffix")
+        && !args.is_present("contains")
+    {
+        return Err(Error::Argument(
+            "--base requires --pattern, --patterns-file, --prefix, --suffix or --contains"
+                .to_string(),
+        ));
+    }
+    if !(2..=36).contains(&base) {
+        return Err(Error::Argument(format!(
+            "--base must be between 2 and 36, got {}",
+            base
+        )));
+    }
+
+    let mut goals = Vec::new();
+    let matcher_base = match (injected_key, args.value_of("pattern")) {
+        (Some(_), _) => PubkeyMatcher::accept_all(),
+        (None, pattern) => {
+            let mut patterns = Vec::new();
+            let exact_length = match args.value_of("exact_length") {
+                Some(s) => Some(parse_value(s, "--exact-length")?),
+                None => None,
+            };
+            let min_length: Option<usize> = match args.value_of("min_length") {
+                Some(s) => Some(parse_value(s, "--min-length")?),
+                None => None,
+            };
+            let target_length: Option<usize> = match args.value_of("target_length") {
+                Some(s) => Some(parse_value(s, "--target-length")?),
+                None => None,
+            };
+            let like = args.value_of("like");
+            let prefix = match args.value_of("prefix") {
+                Some(s) => Some(parse_argument(s, "--prefix", |s| parse_prefix(s, base))?),
+                None => None,
+            };
+            let contains = match args.value_of("contains") {
+                Some(s) => Some(parse_argument(s, "--contains", |s| {
+                    parse_contains(s, base)
+                })?),
+                None => None,
+            };
+            let regex = match args.value_of("regex") {
+                Some(s) => Some(parse_argument(s, "--regex", regex_condition)?),
+                None => None,
+            };
+            let suffix = match args.value_of("suffix") {
+                Some(s) => Some(parse_argument(s, "--suffix", |s| parse_suffix(s, base))?),
+                None => None,
+            };
+            let lucky = lucky_patterns(args.value_of("lucky"), args.value_of("unlucky")).map_err(
+                |error| Error::Argument(format!("Invalid lucky or unlucky numbers: {}", error)),
+            )?;
+            let target = match args.value_of("target") {
+                Some(s) => Some(parse_address(s).map_err(|error| {
+                    Error::Argument(format!("Invalid --target address: {}", error))
+                })?),
+                None => None,
+            };
+            let grouping: Option<Grouping> = match args.value_of("grouped_pattern") {
+                Some(s) => Some(s.parse().map_err(Error::Argument)?),
+                None => None,
+            };
+            let monotonic: Option<Monotonic> = match args.value_of("monotonic") {
+                Some(s) => Some(s.parse().map_err(Error::Argument)?),
+                None => None,
+            };
+            let hash_zeros = match args.value_of("hash_leading_zeros") {
+                Some(s) => Some(
+                    hash_leading_zeros(parse_value(s, "--hash-leading-zeros")?)
+                        .map_err(Error::Argument)?,
+                ),
+                None => None,
+            };
+            let patterns_file = args.value_of("patterns_file");
+            if (pattern.is_none()
+                && patterns_file.is_none()
+                && exact_length.is_none()
+                && target_length.is_none()
+                && like.is_none()
+                && target.is_none()
+                && grouping.is_none()
+                && monotonic.is_none()
+                && hash_zeros.is_none()
+                && lucky.is_empty()
+                && prefix.is_none()
+                && suffix.is_none()
+                && contains.is_none()
+                && regex.is_none()
+                && !args.is_present("mirror"))
+                || args.occurrences_of("length") != 0
+            {
+                patterns.push(Pattern::Condition(match min_length {
+                    Some(min_length) => {
+                        if min_length == 0 || min_length > max_length {
+                            return Err(Error::Argument(format!(
+                                "--min-length must be between 1 and LENGTH ({})",
+                                max_length
+                            )));
+                        }
+                        Condition::LengthRange(min_length, max_length)
+                    }
+                    None => Condition::MaxLength(max_length),
+                }));
+            } else if let Some(min_length) = min_length {
+                if min_length == 0 || min_length > MAX_ADDRESS_LENGTH {
+                    return Err(Error::Argument(format!(
+                        "--min-length must be between 1 and {}",
+                        MAX_ADDRESS_LENGTH
+                    )));
+                }
+                patterns.push(Pattern::Condition(Condition::LengthRange(
+                    min_length,
+                    MAX_ADDRESS_LENGTH,
+                )));
+            }
+            if let Some(pattern) = pattern {
+                let matcher = PubkeyMatcher::parse_in_base(pattern, base)
+                    .map_err(|error| Error::Argument(format!("Invalid pattern: {}", error)))?;
+                patterns.push(matcher.pattern().clone());
+            }
+            if let Some(path) = patterns_file {
+                let content = fs::read_to_string(path).map_err(|error| {
+                    Error::Argument(format!("Failed to read patterns file {}: {}", path, error))
+                })?;
+                goals = parse_patterns_file(&content, base).map_err(|error| {
+                    Error::Argument(format!("Invalid patterns file {}: {}", path, error))
+                })?;
+                patterns.push(Pattern::Any(
+                    goals
+                        .iter()
+                        .map(|goal| goal.matcher.pattern().clone())
+                        .collect(),
+                ));
+            }
+            if let Some(exact_length) = exact_length {
+                if exact_length == 0 || exact_length > MAX_ADDRESS_LENGTH {
+                    return Err(Error::Argument(format!(
+                        "--exact-length must be between 1 and {}",
+                        MAX_ADDRESS_LENGTH
+                    )));
+                }
+                patterns.push(Pattern::Condition(Condition::ExactLength(exact_length)));
+            }
+            if let Some(target_length) = target_length {
+                if target_length == 0 || target_length > MAX_ADDRESS_LENGTH {
+                    return Err(Error::Argument(format!(
+                        "--target-length must be between 1 and {}",
+                        MAX_ADDRESS_LENGTH
+                    )));
+                }
+                let tolerance = parse_value(
+                    args.value_of("length_tolerance").unwrap_or("0"),
+                    "--length-tolerance",
+                )?;
+                patterns.push(Pattern::Condition(length_band(target_length, tolerance)));
+            }
+            if let Some(address) = like {
+                let prefix_len = parse_value(
+                    args.value_of("like_prefix_len").unwrap_or("6"),
+                    "--like-prefix-len",
+                )?;
+                let suffix_len = parse_value(
+                    args.value_of("like_suffix_len").unwrap_or("4"),
+                    "--like-suffix-len",
+                )?;
+                let conditions =
+                    like_conditions(address, prefix_len, suffix_len).map_err(|error| {
+                        Error::Argument(format!("Invalid --like address: {}", error))
+                    })?;
+                patterns.extend(conditions.into_iter().map(Pattern::Condition));
+            }
+            if let Some(rules) = args.value_of("digit_count") {
+                let conditions = parse_digit_count_rules(rules).map_err(|error| {
+                    Error::Argument(format!("Invalid digit count rules: {}", error))
+                })?;
+                patterns.extend(conditions.into_iter().map(Pattern::Condition));
+            }
+            if args.is_present("luhn") {
+                patterns.push(Pattern::Condition(Condition::Luhn));
+            }
+            if let Some(grouping) = grouping {
+                patterns.push(Pattern::Condition(Condition::Grouped(grouping)));
+            }
+            if args.is_present("mirror") {
+                patterns.push(Pattern::Condition(Condition::Mirror));
+            }
+            if let Some(direction) = monotonic {
+                patterns.push(Pattern::Condition(Condition::Monotonic(direction)));
+            }
+            if let Some(condition) = hash_zeros {
+                patterns.push(Pattern::Condition(condition));
+            }
+            patterns.extend(
+                prefix
+                    .into_iter()
+                    .chain(suffix)
+                    .chain(contains)
+                    .chain(regex)
+                    .map(Pattern::Condition),
+            );
+            patterns.extend(lucky);
+            if let Some(target) = target {
+                let digits = target.to_string();
+                patterns.push(Pattern::Condition(Condition::ExactLength(digits.len())));
+                patterns.push(Pattern::Condition(Condition::Prefix(digits)));
+            }
+            if patterns.is_empty() {
+                return Err(Error::Argument(
+                    "--like without shared digits matches every address".to_string(),
+                ));
+            }
+            let matcher = if patterns.len() == 1 {
+                PubkeyMatcher::from_pattern(patterns.pop().unwrap())
+            } else {
+                PubkeyMatcher::from_pattern(Pattern::All(patterns))
+            };
+            match target {
+                Some(target) => matcher.with_target(target),
+                None => matcher,
+            }
+        }
+    };
+    if !matcher_base.can_match() {
+        return Err(Error::Argument(
+            "The search can never match an address. Check the length and pattern.".to_string(),
+        ));
+    }
     let estimated_attempts = matcher_base.estimated_attempts();
+    let match_probability = matcher_base.match_probability();
+    let estimate_is_bound = !matcher_base.unsampled_conditions().is_empty();
+    if args.is_present("estimate_only") {
+        let threads = resolve_thread_count(
+            match args.value_of("cpu_threads") {
+                Some(s) => Some(parse_value(s, "--cpu-threads")?),
+                None => None,
+            },
+            num_cpus::get(),
+        );
+        if threads == 0 {
+            return Err(Error::Argument(
+                "--estimate-only measures the CPU and needs at least one CPU thread".to_string(),
+            ));
+        }
+        // Assumes the threads scale linearly
+        let keys_per_second =
+            threads as f64 * measure_cpu_rate(generate_key_type(&args), Duration::from_secs(1))?;
+        if args.value_of("estimate_format") == Some("json") {
+            println!(
+                "{}",
+                estimate_json(
+                    &estimated_attempts,
+                    match_probability,
+                    keys_per_second,
+                    estimate_is_bound
+                )
+            );
+        } else {
+            warn_unsampled_conditions(&matcher_base);
+            println!("Searching for: {}", matcher_base.describe());
+            println!(
+                "Estimated attempts needed: {}",
+                estimate_text(estimated_attempts.to_string(), estimate_is_bound)
+            );
+            println!(
+                "Median attempts: {}, 95% of searches within {} attempts",
+                estimate_text(
+                    format!("{:.0}", attempts_quantile(match_probability, 0.5)),
+                    estimate_is_bound
+                ),
+                estimate_text(
+                    format!("{:.0}", attempts_quantile(match_probability, 0.95)),
+                    estimate_is_bound
+                )
+            );
+            println!(
+                "Estimated time: {} at {:.1} keys/s with {} CPU threads",
+                estimate_text(
+                    format!("{:.1}s", 1.0 / match_probability / keys_per_second),
+                    estimate_is_bound
+                ),
+                keys_per_second,
+                threads
+            );
+        }
+        return Ok(());
+    }
     let matcher_base = Arc::new(matcher_base);
-    let limit = args
-        .value_of("limit")
+    let limit = parse_value(args.value_of("limit").unwrap(), "--limit")?;
+    let grace =
+        match args.value_of("grace") {
+            Some(s) => Some(parse_duration(s).map_err(|error| {
+                Error::Argument(format!("Invalid value for --grace: {}", error))
+            })?),
+            None => None,
+        };
+    if grace.is_some() && limit == 0 {
+        return Err(Error::Argument("--grace requires a limit".to_string()));
+    }
+    let sort_output = args.is_present("sort_output");
+    if sort_output && (limit == 0 || limit > MAX_SORTED_OUTPUT) {
+        return Err(Error::Argument(format!(
+            "--sort-output requires a limit between 1 and {}",
+            MAX_SORTED_OUTPUT
+        )));
+    }
+    let max_attempts: Option<u64> = match args.value_of("max_attempts") {
+        Some(s) => Some(parse_value(s, "--max-attempts")?),
+        None => None,
+    };
+    // u64 such that long runs do not overflow on 32 bit targets
+    let attempts_base = Arc::new(AtomicU64::new(0));
+    let stop_base = Arc::new(AtomicBool::new(false));
+    let paused_base = Arc::new(AtomicBool::new(false));
+    let output_progress = !args.is_present("no_progress");
+    let events = match args.value_of("events_fd") {
+        Some(s) => Events::to_fd(parse_value(s, "--events-fd")?)?,
+        None => Events::disabled(),
+    };
+    let rate_log = match args.value_of("rate_log") {
+        Some(path) => {
+            let interval = parse_duration(args.value_of("rate_log_interval").unwrap_or("10s"))
+                .map_err(|error| {
+                    Error::Argument(format!("Invalid value for --rate-log-interval: {}", error))
+                })?;
+            if interval == Duration::from_secs(0) {
+                return Err(Error::Argument(
+                    "--rate-log-interval must be at least one second".to_string(),
+                ));
+            }
+            Some((open_rate_log(Path::new(path))?, interval))
+        }
+        None => None,
+    };
+    let watts: Option<f64> = match args.value_of("watts") {
+        Some(s) => Some(parse_value(s, "--watts")?),
+        None => None,
+    };
+    let cost_per_kwh: Option<f64> = match args.value_of("cost_per_kwh") {
+        Some(s) => Some(parse_value(s, "--cost-per-kwh")?),
+        None => None,
+    };
+    let show_attempt = args.is_present("show_attempt");
+    let strict_derivation = args.is_present("strict_derivation");
+    let count_attempts = output_progress
+        || max_attempts.is_some()
+        || show_attempt
+        || rate_log.is_some()
+        || events.is_enabled()
+        || args.is_present("state_file")
+        || args.is_present("group_by_difficulty");
+    let human_numbers = args.is_present("human_numbers");
+    let format_number = move |number: String| {
+        if human_numbers {
+            group_thousands(&number)
+        } else {
+            number
+        }
+    };
+    let output = OutputOptions {
+        simple_output: args.is_present("simple_output"),
+        show_pubkey: args.is_present("show_pubkey"),
+        show_entropy: args.is_present("show_entropy"),
+        recipe: args.is_present("recipe"),
+        pubkey_only: args.is_present("output_pubkey_only"),
+        display_base: if args.is_present("display_base") {
+            Some(base)
+        } else {
+            None
+        },
+        uri: args.is_present("uri"),
+    };
+    if output.show_entropy && injected_key.is_some() {
+        eprintln!(
+            "Warning: keys derived from --test-inject-key are not random. The reported entropy does not apply."
+        );
+    }
+    // Only relevant for humans looking at a terminal, not for scripts
+    if args.is_present("confirm_secrets")
+        && !output.simple_output
+        && !output.pubkey_only
+        && atty::is(atty::Stream::Stdout)
+        && atty::is(atty::Stream::Stdin)
+        && !confirm_secrets()?
+    {
+        return Err(Error::Cancelled);
+    }
+    // Swapped out secrets can end up on disk, possibly long after the search
+    if !output.pubkey_only && !args.is_present("i_understand_swap_risk") {
+        match swap_active() {
+            Some(true) => {
+                return Err(Error::Argument(
+                    "Swap is active, so private keys may be written to disk. Disable swap (e.g. \"sudo swapoff -a\") or pass --i-understand-swap-risk.".to_string(),
+                ))
+            }
+            Some(false) => {}
+            None => eprintln!(
+                "Warning: could not check whether swap is active. Private keys may be written to disk."
+            ),
+        }
+    }
+    let summary_every = match args.value_of("summary_every") {
+        Some(s) => match parse_value(s, "--summary-every")? {
+            0 => {
+                return Err(Error::Argument(
+                    "--summary-every must be at least 1".to_string(),
+                ))
+            }
+            n => Some(n),
+        },
+        None => None,
+    };
+    let json_lines = match args.value_of("socket") {
+        Some(path) => Some(connect_socket(path)?),
+        None => None,
+    };
+    let output_dir = args.value_of("output_dir").map(PathBuf::from);
+    if let Some(ref dir) = output_dir {
+        fs::create_dir_all(dir)?;
+    }
+    let backpressure = args
+        .value_of("on_backpressure")
         .unwrap()
         .parse()
-        .expect("Failed to parse limit option");
-    let found_n_base = Arc::new(AtomicUsize::new(0));
-    let attempts_base = Arc::new(AtomicUsize::new(0));
-    let output_progress = !args.is_present("no_progress");
-    let simple_output = args.is_present("simple_output");
-    let _generate_passphrase = args.is_present("generate_passphrase");
+        .map_err(Error::Argument)?;
+    let rng_source: RngSource = args
+        .value_of("rng")
+        .unwrap()
+        .parse()
+        .map_err(Error::Argument)?;
+    let replay = match args.value_of("replay") {
+        Some(path) => Some(load_record(&PathBuf::from(path))?),
+        None => None,
+    };
+    if replay.is_some()
+        && (args.occurrences_of("seed_increment_bits") != 0
+            || args.occurrences_of("cpu_batch") != 0)
+    {
+        return Err(Error::Argument(
+            "--replay uses the recorded --seed-increment-bits and --cpu-batch".to_string(),
+        ));
+    }
+    let seed_increment_bits: usize = match replay {
+        Some(ref record) => record.seed_increment_bits,
+        None => parse_value(
+            args.value_of("seed_increment_bits").unwrap(),
+            "--seed-increment-bits",
+        )?,
+    };
+    if seed_increment_bits == 0 || seed_increment_bits > 256 {
+        return Err(Error::Argument(
+            "--seed-increment-bits must be between 1 and 256".to_string(),
+        ));
+    }
+    // With --target, the closest candidate is reported at the end
+    let closest_at_end = args.is_present("target");
+    let stream_best = args.is_present("stream_best") || closest_at_end;
+    let best_score_base = Arc::new(AtomicU64::new(u64::MAX));
+    let gen_key_type = generate_key_type(&args);
 
-    let gen_key_type;
-    if args.is_present("generate_keypair") {
-        gen_key_type = GenerateKeyType::PrivateKey;
-    } else {
-        gen_key_type = GenerateKeyType::LiskPassphrase;
+    let cpu_batch: usize = match replay {
+        Some(ref record) => record.cpu_batch,
+        None => parse_value(args.value_of("cpu_batch").unwrap(), "--cpu-batch")?,
+    };
+    if cpu_batch == 0 {
+        return Err(Error::Argument(
+            "--cpu-batch must be at least 1".to_string(),
+        ));
+    }
+    let cpu_threads = match (injected_key, &replay) {
+        (Some(_), _) => 1,
+        (None, Some(record)) => record.threads.len(),
+        (None, None) => resolve_thread_count(
+            match args.value_of("cpu_threads") {
+                Some(s) => Some(parse_value(s, "--cpu-threads")?),
+                None => None,
+            },
+            num_cpus::get(),
+        ),
+    };
+    let use_gpu = args.is_present("gpu") && injected_key.is_none();
+    #[cfg(not(feature = "gpu"))]
+    {
+        if use_gpu {
+            return Err(Error::GpuUnavailable);
+        }
+    }
+    if cpu_threads == 0 && !use_gpu {
+        return Err(Error::Argument(
+            "No computation devices specified. Use --cpu-threads with a value above 0 or --gpu."
+                .to_string(),
+        ));
+    }
+    if let Some(s) = args.value_of("preview") {
+        let count = parse_value(s, "--preview")?;
+        if !preview(&matcher_base, gen_key_type, rng_source, count)? {
+            return Err(Error::Cancelled);
+        }
+    }
+    let state_file = args.value_of("state_file").map(|path| {
+        Arc::new(StateFile::open(
+            PathBuf::from(path),
+            &matcher_base.describe(),
+        ))
+    });
+    let run_start = Instant::now();
+    let (solution_sender, solution_receiver) = sync_channel(SOLUTION_QUEUE_SIZE);
+    let output_params = OutputParams {
+        options: output,
+        output_progress,
+        limit,
+        grace,
+        output_dir,
+        force: args.is_present("force"),
+        attempts: if count_attempts {
+            Some(attempts_base.clone())
+        } else {
+            None
+        },
+        json_lines,
+        summary_every,
+        closest_at_end,
+        sorted: if sort_output {
+            Some(Vec::with_capacity(limit))
+        } else {
+            None
+        },
+        start_time: run_start,
+        threads: cpu_threads + use_gpu as usize,
+        found_banner: !args.is_present("no_found_banner"),
+        events: events.clone(),
+        tiers: if args.is_present("group_by_difficulty") {
+            Some(DifficultyTiers::new())
+        } else {
+            None
+        },
+        stop: stop_base.clone(),
+        state_file: state_file.clone(),
+        goals,
+    };
+    let output_thread = thread::spawn(move || output_solutions(solution_receiver, output_params));
+    if args.is_present("group_by_difficulty") {
+        stop_on_interrupt(stop_base.clone());
     }
 
-    let cpu_threads = args
-        .value_of("cpu_threads")
-        .map(|s| s.parse().expect("Failed to parse thread count option"))
-        .unwrap_or_else(|| num_cpus::get() - 1);
+    let checkpoint = args.value_of("checkpoint").map(PathBuf::from);
+    let mut saved_positions = match checkpoint {
+        Some(ref path) => load_checkpoint(path)?,
+        None => Vec::new(),
+    }
+    .into_iter();
+    let mut positions = Vec::new();
     let mut thread_handles = Vec::with_capacity(cpu_threads);
-    eprintln!("Estimated attempts needed: {}", estimated_attempts);
-    for _ in 0..cpu_threads {
-        let mut rng = OsRng::new().expect("Failed to get RNG for seed");
-        let mut key_or_seed = [0u8; 32];
-        rng.fill_bytes(&mut key_or_seed);
+    events.emit(
+        "started",
+        &format!("\"threads\":{},\"gpu\":{}", cpu_threads, use_gpu),
+    );
+    eprintln!("Searching for: {}", matcher_base.describe());
+    eprintln!(
+        "Estimated attempts needed: {}",
+        estimate_text(
+            format_number(estimated_attempts.to_string()),
+            estimate_is_bound
+        )
+    );
+    events.emit(
+        "estimate",
+        &format!(
+            "\"pattern\":{},\"expectedAttempts\":{},\"lowerBound\":{}",
+            json_string(&matcher_base.describe()),
+            estimated_attempts,
+            estimate_is_bound
+        ),
+    );
+    for (description, attempts, lower_bound) in matcher_base.estimate_breakdown() {
+        eprintln!(
+            "  {} alone: {}",
+            description,
+            estimate_text(format_number(attempts.to_string()), lower_bound)
+        );
+    }
+    warn_unsampled_conditions(&matcher_base);
+    let record_path = args.value_of("record").map(PathBuf::from);
+    if record_path.is_some() && use_gpu {
+        eprintln!("Warning: --record only records the CPU threads, not the GPU");
+    }
+    let mut thread_records = Vec::new();
+    // Starting points of all CPU threads, which must differ to not search the same keys twice
+    let mut seeds = Vec::with_capacity(cpu_threads);
+    for thread in 0..cpu_threads {
+        let replayed = replay.as_ref().map(|record| record.threads[thread]);
+        // Recorded threads re-randomize with a seeded generator, such that the key sequence
+        // is repeatable
+        let rng_seed = match replayed {
+            Some(thread_record) => Some(thread_record.rng_seed),
+            None if record_path.is_some() => {
+                let mut rng_seed = [0u8; 32];
+                new_rng(RngSource::Os)?.fill_bytes(&mut rng_seed);
+                Some(rng_seed)
+            }
+            None => None,
+        };
+        let mut rng = match rng_seed {
+            Some(ref rng_seed) => seeded_rng(rng_seed),
+            None => new_rng(rng_source)?,
+        };
+        let mut key_or_seed = match (injected_key, replayed, saved_positions.next()) {
+            (Some(key), _, _) => key,
+            (None, Some(thread_record), _) => thread_record.start,
+            (None, None, Some(key)) => {
+                if seeds.contains(&key) {
+                    return Err(Error::Argument(
+                        "The checkpoint contains the same position twice".to_string(),
+                    ));
+                }
+                key
+            }
+            (None, None, None) => new_seed(&seeds, &mut *new_rng(rng_source)?)?,
+        };
+        seeds.push(key_or_seed);
+        if let Some(rng_seed) = rng_seed {
+            thread_records.push(ThreadRecord {
+                start: key_or_seed,
+                rng_seed,
+            });
+        }
+        let position = checkpoint
+            .as_ref()
+            .map(|_| Arc::new(Mutex::new(key_or_seed)));
+        if let Some(ref position) = position {
+            positions.push(position.clone());
+        }
         let params = ThreadParams {
-            limit,
-            output_progress,
-            simple_output,
             generate_key_type: gen_key_type.clone(),
             matcher: matcher_base.clone(),
-            found_n: found_n_base.clone(),
             attempts: attempts_base.clone(),
+            count_attempts,
+            max_attempts,
+            stop: stop_base.clone(),
+            paused: paused_base.clone(),
+            stream_best,
+            best_score: best_score_base.clone(),
+            show_attempt,
+            strict_derivation,
+            solutions: solution_sender.clone(),
+            backpressure,
         };
-        thread_handles.push(thread::spawn(move || loop {
-            if check_solution(&params, key_or_seed) {
-                rng.fill_bytes(&mut key_or_seed);
-            } else {
-                if output_progress {
-                    params.attempts.fetch_add(1, atomic::Ordering::Relaxed);
+        thread_handles.push(thread::spawn(move || {
+            let mut unpublished = 0;
+            let mut batch = Vec::with_capacity(cpu_batch);
+            let mut public_keys = Vec::with_capacity(cpu_batch);
+            while wait_while_paused(&params) && claim_attempts(&params, cpu_batch as u64) {
+                if let Some(ref position) = position {
+                    unpublished += cpu_batch as u64;
+                    if unpublished >= PUBLISH_INTERVAL {
+                        *position.lock().unwrap() = key_or_seed;
+                        unpublished = 0;
+                    }
                 }
-                for byte in key_or_seed.iter_mut().rev() {
-                    *byte = byte.wrapping_add(1);
-                    if *byte != 0 {
-                        break;
+                batch.clear();
+                for _ in 0..cpu_batch {
+                    batch.push(key_or_seed);
+                    if increment_low_bits(&mut key_or_seed, seed_increment_bits)
+                        && seed_increment_bits < 256
+                    {
+                        randomize_high_bits(&mut key_or_seed, seed_increment_bits, &mut *rng);
                     }
                 }
+                // Derive the whole batch before matching
+                public_keys.clear();
+                public_keys.extend(
+                    batch
+                        .iter()
+                        .map(|&key| secret_to_pubkey(key, params.generate_key_type)),
+                );
+                let mut matched = false;
+                for (&key_material, &public_key) in batch.iter().zip(public_keys.iter()) {
+                    matched |= check_public_key(&params, key_material, public_key);
+                }
+                if matched && injected_key.is_none() {
+                    rng.fill_bytes(&mut key_or_seed);
+                }
             }
         }));
     }
-    let mut gpu_thread = None;
-    if args.is_present("gpu") {
-        let gpu_platform = args
-            .value_of("gpu_platform")
-            .unwrap()
-            .parse()
-            .expect("Failed to parse GPU platform index");
-        let gpu_device = args
-            .value_of("gpu_device")
-            .unwrap()
-            .parse()
-            .expect("Failed to parse GPU device index");
-        let gpu_threads = args
-            .value_of("gpu_threads")
-            .unwrap()
-            .parse()
-            .expect("Failed to parse GPU threads option");
-        let gpu_local_work_size = args.value_of("gpu_local_work_size").map(|s| {
-            s.parse()
-                .expect("Failed to parse GPU local work size option")
+    if let Some(path) = record_path {
+        let record = Record {
+            seed_increment_bits,
+            cpu_batch,
+            threads: thread_records,
+        };
+        save_record(
+            &path,
+            &record,
+            &format!("Searching for: {}", matcher_base.describe()),
+        )?;
+    }
+    if let Some(path) = checkpoint {
+        let interval = parse_duration(args.value_of("checkpoint_interval").unwrap_or("60s"))
+            .map_err(|error| {
+                Error::Argument(format!(
+                    "Invalid value for --checkpoint-interval: {}",
+                    error
+                ))
+            })?;
+        if interval == Duration::from_secs(0) {
+            return Err(Error::Argument(
+                "--checkpoint-interval must be at least one second".to_string(),
+            ));
+        }
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            let keys: Vec<[u8; 32]> = positions
+                .iter()
+                .map(|position| *position.lock().unwrap())
+                .collect();
+            if let Err(error) = save_checkpoint(&path, &keys) {
+                eprintln!("Failed to save checkpoint: {}", error);
+            }
+        });
+    }
+    if let Some(ref state_file) = state_file {
+        let state_file = state_file.clone();
+        let attempts = attempts_base.clone();
+        thread::spawn(move || loop {
+            thread::sleep(STATE_SAVE_INTERVAL);
+            let attempts = attempts.load(atomic::Ordering::Relaxed);
+            if let Err(error) = state_file.save(attempts, run_start.elapsed()) {
+                eprintln!("Failed to save state file: {}", error);
+            }
         });
+    }
+    let mut gpu_thread = None;
+    if use_gpu {
+        let gpu_platform = parse_value(args.value_of("gpu_platform").unwrap(), "--gpu-platform")?;
+        let gpu_device = parse_value(args.value_of("gpu_device").unwrap(), "--gpu-device")?;
+        let gpu_threads = parse_value(args.value_of("gpu_threads").unwrap(), "--gpu-threads")?;
+        let gpu_build_options =
+            parse_gpu_build_options(args.value_of("gpu_build_opts").unwrap_or(""))
+                .map_err(|error| Error::Argument(format!("Invalid --gpu-build-opts: {}", error)))?;
+        let gpu_local_work_size = match args.value_of("gpu_local_work_size") {
+            Some(s) => Some(parse_value(s, "--gpu-local-work-size")?),
+            None => None,
+        };
+        let gpu_max_address_value = matcher_base.gpu_max_address_value().ok_or_else(|| {
+            Error::Argument(
+                "The GPU only supports searching for a max length. Use the CPU for other patterns."
+                    .to_string(),
+            )
+        })?;
         let mut key_base = [0u8; 32];
         let params = ThreadParams {
-            limit,
-            output_progress,
-            simple_output,
             generate_key_type: gen_key_type.clone(),
             matcher: matcher_base.clone(),
-            found_n: found_n_base.clone(),
             attempts: attempts_base.clone(),
+            count_attempts,
+            max_attempts,
+            stop: stop_base.clone(),
+            paused: paused_base.clone(),
+            stream_best,
+            best_score: best_score_base.clone(),
+            show_attempt,
+            strict_derivation,
+            solutions: solution_sender.clone(),
+            backpressure,
         };
         let mut gpu = Gpu::new(
             gpu_platform,
             gpu_device,
             gpu_threads,
             gpu_local_work_size,
-            max_address(max_length),
+            gpu_max_address_value,
             gen_key_type,
-        )
-        .unwrap();
-        gpu_thread = Some(thread::spawn(move || {
-            let mut rng = OsRng::new().expect("Failed to get RNG for seed");
-            loop {
-                rng.fill_bytes(&mut key_base);
-                let found = gpu
-                    .compute(&key_base)
-                    .expect("Failed to run GPU computation");
-                if output_progress {
-                    params
-                        .attempts
-                        .fetch_add(gpu_threads, atomic::Ordering::Relaxed);
+            &gpu_build_options,
+        )?;
+        // may be lower than requested to fit the device
+        let gpu_threads = gpu.threads();
+        events.emit("gpu_ready", &format!("\"threads\":{}", gpu_threads));
+        if args.is_present("gpu_auto_batch") {
+            gpu.enable_auto_batch();
+        }
+        let pubkey_only = output.pubkey_only;
+        gpu_thread = Some(thread::spawn(move || -> Result<(), Error> {
+            let mut rng = new_rng(rng_source)?;
+            while wait_while_paused(&params) && claim_attempts(&params, gpu.threads() as u64) {
+                if params.stream_best {
+                    // Only ask the GPU for candidates that beat the best one so far
+                    let best_score = params.best_score.load(atomic::Ordering::Relaxed);
+                    let max_address_value = gpu_max_address_value.max(best_score.saturating_sub(1));
+                    gpu.set_max_address_value(max_address_value)?;
                 }
+                rng.fill_bytes(&mut key_base);
+                let found = gpu.compute(&key_base)?;
 
                 if let Some(found_private_key) = found {
-                    if !check_solution(&params, found_private_key) {
+                    if !check_solution(&params, found_private_key) && !params.stream_best {
                         eprintln!(
-                            "GPU returned non-matching solution: {}",
-                            hex::encode_upper(&found_private_key)
+                            "{}",
+                            non_matching_solution_message(
+                                found_private_key,
+                                params.generate_key_type,
+                                pubkey_only
+                            )
                         );
                     }
                 } else {
                     // just continue
                 }
             }
+            Ok(())
         }));
     }
+    // Line buffered, so the user has to press Enter after "p"
+    if atty::is(atty::Stream::Stdin) {
+        eprintln!("Enter \"p\" to pause or resume the search");
+        let paused = paused_base.clone();
+        thread::spawn(move || {
+            let stdin = io::stdin();
+            for line in stdin.lock().lines() {
+                match line {
+                    Ok(ref line) if line.trim() == "p" => {
+                        let was_paused = paused.fetch_xor(true, atomic::Ordering::Relaxed);
+                        if !output_progress {
+                            eprintln!("{}", if was_paused { "Resumed" } else { "Paused" });
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(_) => break,
+                }
+            }
+        });
+    }
+    if events.is_enabled() {
+        let events = events.clone();
+        let attempts = attempts_base.clone();
+        let stop = stop_base.clone();
+        let start_time = Instant::now();
+        thread::spawn(move || {
+            while !stop.load(atomic::Ordering::Relaxed) {
+                thread::sleep(Duration::from_secs(1));
+                let attempts = attempts.load(atomic::Ordering::Relaxed);
+                events.emit(
+                    "progress",
+                    &format!(
+                        "\"attempts\":{},\"keysPerSec\":{:.1}",
+                        attempts,
+                        keys_per_second(attempts, start_time.elapsed())
+                    ),
+                );
+            }
+        });
+    }
+    if let Some((mut file, interval)) = rate_log {
+        let attempts = attempts_base.clone();
+        let stop = stop_base.clone();
+        thread::spawn(move || {
+            let mut last_attempts = attempts.load(atomic::Ordering::Relaxed);
+            let mut last_time = Instant::now();
+            loop {
+                thread::sleep(interval);
+                if stop.load(atomic::Ordering::Relaxed) {
+                    break;
+                }
+                let attempts = attempts.load(atomic::Ordering::Relaxed);
+                let now = Instant::now();
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or(0);
+                let rate = keys_per_second(attempts - last_attempts, now - last_time);
+                if let Err(error) = writeln!(file, "{},{},{:.1}", timestamp, attempts, rate) {
+                    eprintln!("Failed to write rate log: {}", error);
+                    break;
+                }
+                last_attempts = attempts;
+                last_time = now;
+            }
+        });
+    }
     if output_progress {
         let start_time = Instant::now();
-        let attempts = attempts_base;
-        thread::spawn(move || loop {
-            let attempts = attempts.load(atomic::Ordering::Relaxed);
-            let estimated_percent =
-                100. * (attempts as f64) / estimated_attempts.to_f64().unwrap_or(f64::INFINITY);
-            let runtime = start_time.elapsed();
-            let keys_per_second = (attempts as f64)
-                // simplify to .as_millis() when available
-                / (runtime.as_secs() as f64 + runtime.subsec_millis() as f64 / 1000.0);
-            eprint!(
-                "\rTried {} keys (~{:.2}%; {:.1} keys/s)",
-                attempts, estimated_percent, keys_per_second,
-            );
-            thread::sleep(Duration::from_millis(100));
+        let previous_attempts = state_file
+            .as_ref()
+            .map_or(0, |state_file| state_file.previous.attempts);
+        let attempts = attempts_base.clone();
+        let stop = stop_base.clone();
+        let paused = paused_base.clone();
+        thread::spawn(move || {
+            while !stop.load(atomic::Ordering::Relaxed) {
+                let attempts = attempts.load(atomic::Ordering::Relaxed);
+                let total_attempts = previous_attempts + attempts;
+                let estimated_percent = 100. * (total_attempts as f64) * match_probability;
+                let keys_per_second = keys_per_second(attempts, start_time.elapsed());
+                let energy = watts
+                    .and_then(|watts| {
+                        expected_kwh_per_match(match_probability, keys_per_second, watts)
+                    })
+                    .map(|kwh| match cost_per_kwh {
+                        Some(cost_per_kwh) => format!(
+                            "; ~{} kWh, ~{} per match",
+                            format_number(format!("{:.2}", kwh)),
+                            format_number(format!("{:.2}", kwh * cost_per_kwh)),
+                        ),
+                        None => {
+                            format!("; ~{} kWh per match", format_number(format!("{:.2}", kwh)))
+                        }
+                    })
+                    .unwrap_or_default();
+                eprint!(
+                    "\rTried {} keys (~{:.2}%; {} keys/s{}){}",
+                    format_number(total_attempts.to_string()),
+                    estimated_percent,
+                    format_number(format!("{:.1}", keys_per_second)),
+                    energy,
+                    if paused.load(atomic::Ordering::Relaxed) {
+                        " [PAUSED]"
+                    } else {
+                        "         "
+                    },
+                );
+                thread::sleep(Duration::from_millis(100));
+            }
         });
     }
     if let Some(gpu_thread) = gpu_thread {
-        gpu_thread.join().expect("Failed to join GPU thread");
+        gpu_thread.join().expect("Failed to join GPU thread")?;
     }
     for handle in thread_handles {
         handle.join().expect("Failed to join thread");
     }
-    eprintln!("No computation devices specified");
-    process::exit(1);
+    // Workers only return when the attempt budget is exhausted or the search was interrupted.
+    // Let the output thread print the remaining solutions.
+    drop(solution_sender);
+    let totals = output_thread.join().expect("Failed to join output thread");
+    let found = totals.found;
+    if output_progress {
+        eprintln!();
+    }
+    let tried = attempts_base.load(atomic::Ordering::Relaxed);
+    let interrupted = INTERRUPTED.load(atomic::Ordering::Relaxed);
+    events.emit(
+        "stopped",
+        &format!(
+            "\"reason\":\"{}\",\"attempts\":{},\"found\":{}",
+            if interrupted { "interrupted" } else { "budget" },
+            tried,
+            found
+        ),
+    );
+    print_summary(tried);
+    if let Some(ref state_file) = state_file {
+        print_state_totals(state_file, tried, run_start.elapsed());
+    }
+    print_time_summary(run_start.elapsed(), cpu_threads + use_gpu as usize);
+    if interrupted {
+        eprintln!(
+            "Interrupted after {} keys with {} matching accounts",
+            format_number(tried.to_string()),
+            found
+        );
+        return Ok(());
+    }
+    // The closest account is the result of --target, even if it is not the target itself
+    if found == 0 && !totals.closest {
+        return Err(Error::AttemptsExhausted(tried));
+    }
+    if found == 0 {
+        eprintln!(
+            "Attempt budget exhausted after {} keys, reporting the closest account",
+            format_number(tried.to_string())
+        );
+        return Ok(());
+    }
+    eprintln!(
+        "Attempt budget exhausted after {} keys with {} matching accounts",
+        format_number(tried.to_string()),
+        found
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    // importing names from outer (for mod tests) scope.
+    use super::*;
+    use rand::{SeedableRng, XorShiftRng};
+
+    fn thread_params(max_attempts: Option<u64>) -> ThreadParams {
+        let (solutions, _) = sync_channel(1);
+        ThreadParams {
+            attempts: Arc::new(AtomicU64::new(0)),
+            count_attempts: true,
+            max_attempts,
+            stop: Arc::new(AtomicBool::new(false)),
+            paused: Arc::new(AtomicBool::new(false)),
+            generate_key_type: GenerateKeyType::PrivateKey,
+            matcher: Arc::new(PubkeyMatcher::new(1)),
+            stream_best: false,
+            best_score: Arc::new(AtomicU64::new(u64::MAX)),
+            show_attempt: false,
+            strict_derivation: false,
+            solutions,
+            backpressure: Backpressure::Block,
+        }
+    }
+
+    #[test]
+    fn test_non_matching_solution_message() {
+        let key_material = [0xabu8; 32];
+        let secret = hex::encode_upper(key_material);
+        let public_key =
+            hex::encode_upper(secret_to_pubkey(key_material, GenerateKeyType::PrivateKey));
+        let message =
+            non_matching_solution_message(key_material, GenerateKeyType::PrivateKey, false);
+        assert!(message.contains(&secret));
+        let message =
+            non_matching_solution_message(key_material, GenerateKeyType::PrivateKey, true);
+        assert!(!message.contains(&secret));
+        assert!(message.contains(&public_key));
+    }
+
+    // Returns the same bytes forever
+    struct ConstantRng(u32);
+
+    impl Rng for ConstantRng {
+        fn next_u32(&mut self) -> u32 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_parse_gpu_build_options() {
+        assert_eq!(parse_gpu_build_options(""), Ok(String::new()));
+        assert_eq!(
+            parse_gpu_build_options("  -cl-std=CL1.2   -DFOO=1 "),
+            Ok("-cl-std=CL1.2 -DFOO=1".to_string())
+        );
+        assert!(parse_gpu_build_options("-cl-fast-relaxed-math").is_err());
+        assert!(parse_gpu_build_options("cl-std=CL1.2").is_err());
+        assert!(parse_gpu_build_options("-DX=\"1\"").is_err());
+        assert!(parse_gpu_build_options("-DX;rm").is_err());
+    }
+
+    #[test]
+    fn test_attempts_quantile() {
+        assert_eq!(attempts_quantile(1.0, 0.5), 1.0);
+        assert_eq!(attempts_quantile(0.5, 0.5), 1.0);
+        // ln(0.5) / ln(0.999) = 692.8
+        assert_eq!(attempts_quantile(0.001, 0.5), 693.0);
+        // ln(0.05) / ln(0.999) = 2994.2
+        assert_eq!(attempts_quantile(0.001, 0.95), 2995.0);
+    }
+
+    #[test]
+    fn test_estimate_json() {
+        assert_eq!(
+            estimate_json(&BigInt::from(1000), 0.001, 250.0, false),
+            "{\"expected_attempts\":1000,\"median_attempts\":693,\"p95_attempts\":2995,\"estimated_seconds\":4.0,\"lower_bound\":false}"
+        );
+        assert_eq!(estimate_text("1,000".to_string(), false), "1,000");
+        assert_eq!(estimate_text("1,000".to_string(), true), "at least 1,000");
+    }
+
+    #[test]
+    fn test_open_rate_log() {
+        let dir = std::env::temp_dir().join(format!("lisk-vanity-rate-log-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rates.csv");
+
+        writeln!(open_rate_log(&path).unwrap(), "1,100,10.0").unwrap();
+        writeln!(open_rate_log(&path).unwrap(), "2,200,10.0").unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "timestamp,attempts,keys_per_sec\n1,100,10.0\n2,200,10.0\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_new_seed() {
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let first = new_seed(&[], &mut rng).unwrap();
+        let second = new_seed(&[first], &mut rng).unwrap();
+        assert_ne!(first, second);
+
+        let mut rng = ConstantRng(7);
+        let seed = new_seed(&[], &mut rng).unwrap();
+        assert!(new_seed(&[seed], &mut rng).is_err());
+        assert!(new_seed(&[], &mut ConstantRng(0)).is_err());
+    }
+
+    #[test]
+    fn test_wait_while_paused() {
+        let params = thread_params(None);
+        assert!(wait_while_paused(&params));
+
+        // paused workers wake up when stopped
+        params.paused.store(true, atomic::Ordering::Relaxed);
+        let stop = params.stop.clone();
+        let stopper = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            stop.store(true, atomic::Ordering::Relaxed);
+        });
+        assert!(!wait_while_paused(&params));
+        stopper.join().unwrap();
+    }
+
+    #[test]
+    fn test_claim_attempts() {
+        let params = thread_params(Some(3));
+        assert!(claim_attempts(&params, 1));
+        assert!(claim_attempts(&params, 1));
+        assert!(claim_attempts(&params, 1));
+        assert!(!params.stop.load(atomic::Ordering::Relaxed));
+        assert!(!claim_attempts(&params, 1));
+        assert!(params.stop.load(atomic::Ordering::Relaxed));
+        assert_eq!(params.attempts.load(atomic::Ordering::Relaxed), 3);
+
+        // batches may overshoot
+        let params = thread_params(Some(3));
+        assert!(claim_attempts(&params, 2));
+        assert!(claim_attempts(&params, 2));
+        assert!(!claim_attempts(&params, 2));
+        assert_eq!(params.attempts.load(atomic::Ordering::Relaxed), 4);
+
+        let params = thread_params(None);
+        assert!(claim_attempts(&params, u64::MAX));
+        assert!(!params.stop.load(atomic::Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_expected_kwh_per_match() {
+        assert_eq!(expected_kwh_per_match(0.5, 0.0, 100.0), None);
+        assert_eq!(expected_kwh_per_match(0.0, 1000.0, 100.0), None);
+        // 3600 s at 1000 W
+        let kwh = expected_kwh_per_match(1e-6, 1e6 / 3600.0, 1000.0).unwrap();
+        assert!((kwh - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_increment_low_bits() {
+        let mut key = [0u8; 32];
+        assert!(!increment_low_bits(&mut key, 256));
+        assert_eq!(key[31], 1);
+
+        let mut key = [0xffu8; 32];
+        assert!(increment_low_bits(&mut key, 256));
+        assert_eq!(key, [0u8; 32]);
+
+        // wraps within the low 12 bits and leaves the others alone
+        let mut key = [0xffu8; 32];
+        assert!(increment_low_bits(&mut key, 12));
+        assert_eq!(key[31], 0x00);
+        assert_eq!(key[30], 0xf0);
+        assert_eq!(key[29], 0xff);
+
+        let mut key = [0u8; 32];
+        key[31] = 0xff;
+        assert!(!increment_low_bits(&mut key, 12));
+        assert_eq!(key[31], 0x00);
+        assert_eq!(key[30], 0x01);
+    }
+
+    #[test]
+    fn test_randomize_high_bits() {
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let mut key = [0u8; 32];
+        for _ in 0..10 {
+            randomize_high_bits(&mut key, 12, &mut rng);
+            assert_eq!(key[31], 0);
+            assert_eq!(key[30] & 0x0f, 0);
+        }
+        assert_ne!(key[..30], [0u8; 30]);
+
+        let mut key = [0x55u8; 32];
+        randomize_high_bits(&mut key, 256, &mut rng);
+        assert_eq!(key, [0x55u8; 32]);
+    }
+
+    #[test]
+    fn test_capabilities_json() {
+        let json = capabilities_json();
+        assert!(json.starts_with(&format!("{{\"version\":\"{}\",", env!("CARGO_PKG_VERSION"))));
+        assert!(json.contains(&format!("\"gpu\":{},", cfg!(feature = "gpu"))));
+        assert!(json.contains("\"formats\":[\"human\",\"simple\",\"json\"]"));
+        assert!(json.contains("\"keyTypes\":[\"passphrase\",\"keypair\"]"));
+        assert!(json.contains("\"hashes\":[\"sha256\"]"));
+        // all conditions of the pattern language are listed
+        for keyword in CONDITION_KEYWORDS {
+            assert!(json.contains(&format!("\"{}\"", keyword)));
+        }
+        assert!("len<=3 & prefix:1 & suffix:2 & contains:3 & luhn & mirror & count:7>=1 & groups:equal & monotonic:inc & len==3 & hashzeros:1 & excludes:4"
+            .parse::<PubkeyMatcher>()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_resolve_thread_count() {
+        assert_eq!(resolve_thread_count(None, 8), 7);
+        assert_eq!(resolve_thread_count(None, 2), 1);
+        assert_eq!(resolve_thread_count(None, 1), 1);
+        assert_eq!(resolve_thread_count(None, 0), 1);
+        assert_eq!(resolve_thread_count(Some(3), 8), 3);
+        assert_eq!(resolve_thread_count(Some(16), 1), 16);
+        assert_eq!(resolve_thread_count(Some(0), 8), 0);
+    }
+
+    #[test]
+    fn test_keys_per_second() {
+        assert_eq!(keys_per_second(0, Duration::from_secs(0)), 0.0);
+        assert_eq!(keys_per_second(1000, Duration::from_secs(0)), 0.0);
+        assert_eq!(keys_per_second(1000, Duration::from_millis(500)), 2000.0);
+        // more than 2^32 keys, e.g. after a multi-day GPU run
+        let attempts = 5 * (1u64 << 32);
+        assert_eq!(
+            keys_per_second(attempts, Duration::from_secs(5)),
+            (1u64 << 32) as f64
+        );
+        let attempts = u64::MAX;
+        assert!(keys_per_second(attempts, Duration::from_secs(1)) > 1.8e19);
+    }
 }
@@ -0,0 +1,999 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic;
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use checkpoint::StateFile;
+use derivation::{entropy_bits, pubkey_to_address, GenerateKeyType};
+use error::{exit_with_error, Error};
+use events::{json_string, Events};
+use pubkey_matcher::{address_length, address_space_coverage, render_in_base, PatternGoal};
+use recipe::Recipe;
+use usage::{parallel_efficiency, process_cpu_time};
+
+// Human readable (the default), --simple-output and JSON (--output-dir, --socket)
+pub const OUTPUT_FORMATS: &[&str] = &["human", "simple", "json"];
+
+// Most matches that are buffered for --sort-output
+pub const MAX_SORTED_OUTPUT: usize = 100_000;
+
+// Number of solutions that can be queued for output before workers block or drop solutions
+pub const SOLUTION_QUEUE_SIZE: usize = 256;
+
+#[derive(Clone, Copy)]
+pub struct OutputOptions {
+    pub simple_output: bool,
+    pub show_pubkey: bool,
+    pub show_entropy: bool,
+    pub recipe: bool,
+    // Print only "[public key],[address]" lines without any secret (see --output-pubkey-only)
+    pub pubkey_only: bool,
+    // Base of the additional address line (see --display-base)
+    pub display_base: Option<u32>,
+    // Add the address as a wallet link (see --uri)
+    pub uri: bool,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum SolutionKind {
+    Match,
+    // better than all addresses seen before, but not matching (see --stream-best)
+    BetterCandidate,
+}
+
+#[derive(Clone, Copy)]
+pub struct Solution {
+    pub kind: SolutionKind,
+    pub key_material: [u8; 32],
+    pub key_type: GenerateKeyType,
+    pub public_key: [u8; 32],
+    // The shared attempt counter when the solution was found (see --show-attempt). Only
+    // approximate since workers publish their attempts in batches.
+    pub attempt: Option<u64>,
+}
+
+impl Solution {
+    pub fn address(&self) -> u64 {
+        pubkey_to_address(&self.public_key)
+    }
+
+    pub fn private_key_string(&self) -> String {
+        self.key_type
+            .private_key_string(&self.key_material, &self.public_key)
+    }
+}
+
+// What workers do when the output thread cannot keep up
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Backpressure {
+    Block,
+    Drop,
+}
+
+impl FromStr for Backpressure {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Backpressure, String> {
+        match input {
+            "block" => Ok(Backpressure::Block),
+            "drop" => Ok(Backpressure::Drop),
+            _ => Err(format!("Unknown backpressure mode \"{}\"", input)),
+        }
+    }
+}
+
+pub fn send_solution(
+    sender: &SyncSender<Solution>,
+    backpressure: Backpressure,
+    solution: Solution,
+) {
+    match backpressure {
+        Backpressure::Block => sender
+            .send(solution)
+            .expect("Failed to send solution to output thread"),
+        Backpressure::Drop => {
+            if let Err(TrySendError::Full(dropped)) = sender.try_send(solution) {
+                eprintln!(
+                    "Output is lagging behind. Dropped solution for address {}",
+                    full_address(dropped.address())
+                );
+            }
+        }
+    }
+}
+
+pub fn full_address(address: u64) -> String {
+    format!("{}L", address)
+}
+
+// Link opening the address in a wallet, e.g. "lisk:1234L"
+pub fn lisk_uri(address: u64) -> String {
+    format!("lisk:{}", full_address(address))
+}
+
+// JSON schema of `solution_json`. Keep both in sync.
+pub const SOLUTION_JSON_SCHEMA: &str = r#"{
+  "$schema": "http://json-schema.org/draft-07/schema#",
+  "title": "lisk-vanity solution",
+  "type": "object",
+  "properties": {
+    "address": {
+      "description": "The address including the \"L\" suffix",
+      "type": "string",
+      "pattern": "^[0-9]{1,20}L$"
+    },
+    "addressNumeric": {
+      "description": "The address without suffix. A string since it exceeds the safe integer range of JavaScript.",
+      "type": "string",
+      "pattern": "^[0-9]{1,20}$"
+    },
+    "privateKey": {
+      "description": "The 12 word passphrase, or the 64 byte libsodium secret key in upper case hex with --generate-keypair",
+      "type": "string",
+      "oneOf": [
+        { "pattern": "^[a-z]+( [a-z]+){11}$" },
+        { "pattern": "^[0-9A-F]{128}$" }
+      ]
+    },
+    "publicKey": {
+      "description": "The Ed25519 public key in upper case hex",
+      "type": "string",
+      "pattern": "^[0-9A-F]{64}$"
+    },
+    "attempt": {
+      "description": "The approximate number of attempts before the solution was found, with --show-attempt",
+      "type": "integer",
+      "minimum": 0
+    },
+    "goals": {
+      "description": "The lines of the --patterns-file the address matches",
+      "type": "array",
+      "items": { "type": "integer", "minimum": 1 }
+    }
+  },
+  "required": ["address", "addressNumeric", "privateKey", "publicKey"],
+  "additionalProperties": false
+}"#;
+
+// All values are hex, decimal or lowercase BIP39 words, so no escaping is needed.
+// `goals` are the patterns file lines the solution matches (see --patterns-file).
+pub fn solution_json(solution: &Solution, goals: &[&PatternGoal]) -> String {
+    let attempt = match solution.attempt {
+        Some(attempt) => format!(",\"attempt\":{}", attempt),
+        None => String::new(),
+    };
+    format!(
+        "{{\"address\":\"{}\",\"addressNumeric\":\"{}\",\"privateKey\":\"{}\",\"publicKey\":\"{}\"{}{}}}",
+        full_address(solution.address()),
+        solution.address(),
+        solution.private_key_string(),
+        hex::encode_upper(solution.public_key),
+        attempt,
+        goals_json(goals),
+    )
+}
+
+// The optional "goals" property, e.g. `,"goals":[2,5]`
+fn goals_json(goals: &[&PatternGoal]) -> String {
+    if goals.is_empty() {
+        return String::new();
+    }
+    let lines: Vec<String> = goals.iter().map(|goal| goal.line.to_string()).collect();
+    format!(",\"goals\":[{}]", lines.join(","))
+}
+
+// Writes the solution to "[address]L.json" in `dir`. Existing files are only replaced with `force`.
+pub fn write_solution_file(
+    dir: &Path,
+    solution: &Solution,
+    goals: &[&PatternGoal],
+    force: bool,
+) -> Result<(), Error> {
+    let path = dir.join(format!("{}.json", full_address(solution.address())));
+    let mut options = fs::OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    // The file contains a private key
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&path).map_err(|error| {
+        if error.kind() == io::ErrorKind::AlreadyExists {
+            Error::Io(io::Error::new(
+                error.kind(),
+                format!("Refusing to overwrite {} (use --force)", path.display()),
+            ))
+        } else {
+            Error::Io(error)
+        }
+    })?;
+    writeln!(file, "{}", solution_json(solution, goals))?;
+    Ok(())
+}
+
+// Connects to the Unix domain socket that receives matches as JSON lines (see --socket)
+#[cfg(unix)]
+pub fn connect_socket(path: &str) -> Result<Box<dyn Write + Send>, Error> {
+    Ok(Box::new(UnixStream::connect(path)?))
+}
+
+#[cfg(not(unix))]
+pub fn connect_socket(_path: &str) -> Result<Box<dyn Write + Send>, Error> {
+    Err(Error::Argument(
+        "--socket is only supported on Unix".to_string(),
+    ))
+}
+
+// The output line of --output-pubkey-only, which must not contain secret material
+fn pubkey_only_line(solution: &Solution) -> String {
+    format!(
+        "{},{}",
+        hex::encode_upper(solution.public_key),
+        solution.address()
+    )
+}
+
+// Fails with `BrokenPipe` if the reader closed stdout, e.g. `| head -n 2`.
+// `goals` are the patterns file lines the solution matches (see --patterns-file).
+pub fn print_solution(
+    title: &str,
+    solution: &Solution,
+    options: OutputOptions,
+    goals: &[&PatternGoal],
+) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let secret_key_material = solution.key_material;
+    let public_key = solution.public_key;
+    if options.pubkey_only {
+        writeln!(out, "{}", pubkey_only_line(solution))?;
+    } else if options.simple_output {
+        // The optional columns after the address and public key
+        let mut extra_columns = String::new();
+        if options.uri {
+            extra_columns += &format!(" {}", lisk_uri(solution.address()));
+        }
+        if !goals.is_empty() {
+            let lines: Vec<String> = goals.iter().map(|goal| goal.line.to_string()).collect();
+            extra_columns += &format!(" {}", lines.join(","));
+        }
+        if options.show_pubkey {
+            writeln!(
+                out,
+                "{} {} {}{}",
+                hex::encode_upper(&secret_key_material as &[u8]),
+                pubkey_to_address(&public_key),
+                hex::encode_upper(public_key),
+                extra_columns,
+            )?;
+        } else {
+            writeln!(
+                out,
+                "{} {}{}",
+                hex::encode_upper(&secret_key_material as &[u8]),
+                pubkey_to_address(&public_key),
+                extra_columns,
+            )?;
+        }
+    } else {
+        if !title.is_empty() {
+            writeln!(out, "{}", title)?;
+        }
+        writeln!(
+            out,
+            "Private Key: {}\nAddress:     {}",
+            solution.private_key_string(),
+            full_address(pubkey_to_address(&public_key)),
+        )?;
+        if options.uri {
+            writeln!(out, "URI:         {}", lisk_uri(solution.address()))?;
+        }
+        if !goals.is_empty() {
+            let goals: Vec<String> = goals.iter().map(|goal| goal.describe()).collect();
+            writeln!(out, "Goals:       {}", goals.join(", "))?;
+        }
+        if let Some(base) = options.display_base {
+            writeln!(
+                out,
+                "{:<13}{}",
+                format!("Base {}:", base),
+                render_in_base(solution.address(), base)
+            )?;
+        }
+        if options.show_pubkey {
+            writeln!(out, "Public Key:  {}", hex::encode_upper(public_key))?;
+        }
+        if options.show_entropy {
+            writeln!(out, "Entropy:     {} bits", entropy_bits(solution.key_type))?;
+        }
+        if let Some(attempt) = solution.attempt {
+            writeln!(out, "Attempt:     ~{}", attempt)?;
+        }
+        if options.recipe {
+            let recipe = Recipe {
+                key_type: solution.key_type,
+                key_material: secret_key_material,
+                address: solution.address(),
+            };
+            writeln!(out, "Recipe:      {}", recipe.encode())?;
+        }
+    }
+    Ok(())
+}
+
+// Final statistics of a run, printed to stderr
+pub fn print_summary(attempts: u64) {
+    eprintln!(
+        "Tried {} keys, covering ~{:.2e} of the 2^64 addresses",
+        attempts,
+        address_space_coverage(attempts)
+    );
+}
+
+// Saves the totals including the current session and prints them (see --state-file)
+pub fn print_state_totals(state_file: &StateFile, attempts: u64, elapsed: Duration) {
+    if let Err(error) = state_file.save(attempts, elapsed) {
+        eprintln!("Failed to save state file: {}", error);
+    }
+    let total = state_file.total(attempts, elapsed);
+    eprintln!(
+        "Tried {} keys in {}s over all sessions",
+        total.attempts,
+        total.elapsed.as_secs()
+    );
+}
+
+fn time_summary(wall_time: Duration, cpu_time: Option<Duration>, threads: usize) -> String {
+    let mut summary = format!("Wall-clock time {:.1}s", wall_time.as_secs_f64());
+    if let Some(cpu_time) = cpu_time {
+        summary += &format!(", CPU time {:.1}s", cpu_time.as_secs_f64());
+        if let Some(efficiency) = parallel_efficiency(cpu_time, wall_time, threads) {
+            summary += &format!(
+                " ({:.0}% parallel efficiency with {} threads)",
+                efficiency * 100.0,
+                threads
+            );
+        }
+    }
+    summary
+}
+
+// Wall-clock and CPU time of a run, printed to stderr. `threads` are the search threads.
+pub fn print_time_summary(wall_time: Duration, threads: usize) {
+    eprintln!("{}", time_summary(wall_time, process_cpu_time(), threads));
+}
+
+// Statistics over the matches of a run (see --summary-every)
+struct FindStats {
+    count: usize,
+    difficulty_sum: f64,
+    shortest: Option<u64>,
+}
+
+impl FindStats {
+    fn new() -> FindStats {
+        FindStats {
+            count: 0,
+            difficulty_sum: 0.0,
+            shortest: None,
+        }
+    }
+
+    fn record(&mut self, address: u64) {
+        self.count += 1;
+        // Expected number of attempts to find an address this small
+        self.difficulty_sum += address_space_coverage(address).recip().min(u64::MAX as f64);
+        self.shortest = Some(
+            self.shortest
+                .map_or(address, |shortest| shortest.min(address)),
+        );
+    }
+
+    fn summary(&self, elapsed: Duration) -> String {
+        let seconds = elapsed.as_secs_f64();
+        let rate = if seconds > 0.0 {
+            self.count as f64 / seconds
+        } else {
+            0.0
+        };
+        format!(
+            "Summary: {} matches, average difficulty {:.0}, shortest {}, {:.3} matches/s",
+            self.count,
+            self.difficulty_sum / self.count as f64,
+            self.shortest.map_or("-".to_string(), full_address),
+            rate
+        )
+    }
+}
+
+// Matches grouped by their number of digits, which determines how hard they are to find
+// (see --group-by-difficulty)
+pub struct DifficultyTiers(BTreeMap<usize, Vec<u64>>);
+
+impl DifficultyTiers {
+    pub fn new() -> DifficultyTiers {
+        DifficultyTiers(BTreeMap::new())
+    }
+
+    fn record(&mut self, address: u64) {
+        self.0
+            .entry(address_length(address))
+            .or_default()
+            .push(address);
+    }
+
+    // Shortest, i.e. hardest, addresses first
+    fn report(&self) -> String {
+        let mut report = "Matches by difficulty:".to_string();
+        for (length, addresses) in &self.0 {
+            report.push_str(&format!("\n  {} digits: {} found", length, addresses.len()));
+            for &address in addresses {
+                report.push_str(&format!("\n    {}", full_address(address)));
+            }
+        }
+        report
+    }
+}
+
+pub struct OutputParams {
+    pub options: OutputOptions,
+    pub output_progress: bool,
+    pub limit: usize,
+    pub grace: Option<Duration>,
+    pub output_dir: Option<PathBuf>,
+    pub force: bool,
+    // The shared attempt counter, if attempts are counted
+    pub attempts: Option<Arc<AtomicU64>>,
+    // Receives matches as JSON lines instead of stdout
+    pub json_lines: Option<Box<dyn Write + Send>>,
+    pub summary_every: Option<usize>,
+    // Only output the last better candidate once all workers stopped (see --target)
+    pub closest_at_end: bool,
+    // Matches buffered to be output sorted by address at the end (see --sort-output)
+    pub sorted: Option<Vec<Solution>>,
+    pub start_time: Instant,
+    // Number of search threads, for the parallel efficiency
+    pub threads: usize,
+    // Print "Found matching account!" before matches (see --no-found-banner)
+    pub found_banner: bool,
+    pub events: Events,
+    pub tiers: Option<DifficultyTiers>,
+    // Set once stdout is closed, to stop the workers
+    pub stop: Arc<AtomicBool>,
+    pub state_file: Option<Arc<StateFile>>,
+    // The lines of the patterns file, empty without --patterns-file
+    pub goals: Vec<PatternGoal>,
+}
+
+impl OutputParams {
+    fn exit(&mut self, found: usize) -> ! {
+        let flushed = self.flush_sorted();
+        self.check_stdout(flushed, found);
+        self.print_tiers();
+        self.finish("limit", found);
+    }
+
+    fn finish(&self, reason: &str, found: usize) -> ! {
+        let attempts = self
+            .attempts
+            .as_ref()
+            .map(|attempts| attempts.load(atomic::Ordering::Relaxed));
+        if let Some(attempts) = attempts {
+            if self.output_progress {
+                eprintln!();
+            }
+            print_summary(attempts);
+            if let Some(ref state_file) = self.state_file {
+                print_state_totals(state_file, attempts, self.start_time.elapsed());
+            }
+        }
+        self.events.emit(
+            "stopped",
+            &format!(
+                "\"reason\":\"{}\",\"attempts\":{},\"found\":{}",
+                reason,
+                attempts.map_or("null".to_string(), |attempts| attempts.to_string()),
+                found
+            ),
+        );
+        print_time_summary(self.start_time.elapsed(), self.threads);
+        process::exit(0);
+    }
+
+    // A reader closing stdout, e.g. `| head -n 2`, stops the search like reaching the limit.
+    // Other write errors are fatal.
+    fn check_stdout(&self, result: io::Result<()>, found: usize) {
+        match result {
+            Ok(()) => {}
+            Err(ref error) if error.kind() == io::ErrorKind::BrokenPipe => {
+                self.stop.store(true, atomic::Ordering::Relaxed);
+                self.finish("closed", found);
+            }
+            Err(error) => exit_with_error(&Error::Io(error)),
+        }
+    }
+
+    fn print_tiers(&self) {
+        if let Some(ref tiers) = self.tiers {
+            eprintln!("{}", tiers.report());
+        }
+    }
+
+    // Outputs the buffered matches ordered by address
+    fn flush_sorted(&mut self) -> io::Result<()> {
+        if let Some(mut sorted) = self.sorted.take() {
+            sorted.sort_by_key(|solution| solution.address());
+            for solution in &sorted {
+                self.output_match("Found matching account!", solution)?;
+            }
+            self.sorted = Some(Vec::new());
+        }
+        Ok(())
+    }
+
+    // Fails only if writing to stdout fails
+    fn output_match(&mut self, title: &str, solution: &Solution) -> io::Result<()> {
+        if let Some(ref mut tiers) = self.tiers {
+            tiers.record(solution.address());
+        }
+        let goals = matched_goals(&self.goals, solution);
+        self.events.emit(
+            "found",
+            &format!(
+                "\"address\":{},\"publicKey\":\"{}\"{}",
+                json_string(&full_address(solution.address())),
+                hex::encode_upper(solution.public_key),
+                goals_json(&goals)
+            ),
+        );
+        match self.json_lines {
+            Some(ref mut writer) => {
+                if let Err(error) = writeln!(writer, "{}", solution_json(solution, &goals)) {
+                    exit_with_error(&Error::Io(error));
+                }
+            }
+            None if self.found_banner => print_solution(title, solution, self.options, &goals)?,
+            None => print_solution("", solution, self.options, &goals)?,
+        }
+        self.write_file(solution, &goals);
+        Ok(())
+    }
+
+    fn write_file(&self, solution: &Solution, goals: &[&PatternGoal]) {
+        if let Some(ref dir) = self.output_dir {
+            if let Err(error) = write_solution_file(dir, solution, goals, self.force) {
+                exit_with_error(&error);
+            }
+        }
+    }
+}
+
+fn matched_goals<'a>(goals: &'a [PatternGoal], solution: &Solution) -> Vec<&'a PatternGoal> {
+    goals
+        .iter()
+        .filter(|goal| goal.matcher.matches_address(solution.address()))
+        .collect()
+}
+
+fn is_shorter(solution: &Solution, shortest: &Option<Solution>) -> bool {
+    match shortest {
+        Some(shortest) => address_length(solution.address()) < address_length(shortest.address()),
+        None => true,
+    }
+}
+
+// What was output once all workers stopped before reaching the limit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputTotals {
+    pub found: usize,
+    // Whether the closest candidate was output instead of a match (see --target)
+    pub closest: bool,
+}
+
+// Prints all solutions sent by the workers and exits the process once the limit is reached.
+// Since this is the only consumer, the limit is exact.
+pub fn output_solutions(receiver: Receiver<Solution>, mut params: OutputParams) -> OutputTotals {
+    let mut found_n = 0;
+    let mut shortest: Option<Solution> = None;
+    // Whether `shortest` was found after the limit was reached, i.e. not output yet
+    let mut shortest_is_new = false;
+    let mut grace_deadline: Option<Instant> = None;
+    let start_time = Instant::now();
+    let mut stats = FindStats::new();
+    let mut next_summary = params.summary_every.unwrap_or(0);
+    let mut closest: Option<Solution> = None;
+
+    loop {
+        let solution = match grace_deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+                match receiver.recv_timeout(deadline - now) {
+                    Ok(solution) => solution,
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            None => match receiver.recv() {
+                Ok(solution) => solution,
+                Err(_) => {
+                    let reported_closest = closest.is_some();
+                    if let Some(closest) = closest {
+                        if params.output_progress {
+                            eprintln!();
+                        }
+                        let printed =
+                            print_solution("Closest account:", &closest, params.options, &[]);
+                        params.check_stdout(printed, found_n);
+                    }
+                    let flushed = params.flush_sorted();
+                    params.check_stdout(flushed, found_n);
+                    params.print_tiers();
+                    return OutputTotals {
+                        found: found_n,
+                        closest: reported_closest,
+                    };
+                }
+            },
+        };
+
+        match solution.kind {
+            SolutionKind::BetterCandidate if params.closest_at_end => closest = Some(solution),
+            SolutionKind::BetterCandidate => {
+                if params.output_progress {
+                    eprintln!();
+                }
+                let printed =
+                    print_solution("Found better candidate!", &solution, params.options, &[]);
+                params.check_stdout(printed, found_n);
+            }
+            SolutionKind::Match => {
+                if grace_deadline.is_some() {
+                    // In the grace period after the limit was reached. Only remember strictly shorter matches.
+                    if is_shorter(&solution, &shortest) {
+                        shortest = Some(solution);
+                        shortest_is_new = true;
+                    }
+                    continue;
+                }
+
+                match params.sorted {
+                    Some(ref mut sorted) => sorted.push(solution),
+                    None => {
+                        if params.output_progress {
+                            eprintln!();
+                        }
+                        let printed = params.output_match("Found matching account!", &solution);
+                        params.check_stdout(printed, found_n);
+                    }
+                }
+                if let Some(summary_every) = params.summary_every {
+                    stats.record(solution.address());
+                    if stats.count == next_summary {
+                        eprintln!("{}", stats.summary(start_time.elapsed()));
+                        next_summary += summary_every;
+                    }
+                }
+                if params.grace.is_some() && is_shorter(&solution, &shortest) {
+                    shortest = Some(solution);
+                }
+
+                found_n += 1;
+                if params.limit != 0 && found_n >= params.limit {
+                    match params.grace {
+                        Some(grace) => {
+                            eprintln!(
+                                "Limit reached. Searching for shorter matches for another {} seconds.",
+                                grace.as_secs()
+                            );
+                            grace_deadline = Some(Instant::now() + grace);
+                        }
+                        None => params.exit(found_n),
+                    }
+                }
+            }
+        }
+    }
+
+    let flushed = params.flush_sorted();
+    params.check_stdout(flushed, found_n);
+    let shortest = shortest.expect("No match recorded");
+    if params.output_progress {
+        eprintln!();
+    }
+    let printed = if shortest_is_new {
+        params.output_match("Best matching account:", &shortest)
+    } else if params.json_lines.is_none() && !params.options.simple_output {
+        // Repeat the match that was output before as the result of the search. Machine readable
+        // output lists every match exactly once.
+        let goals = matched_goals(&params.goals, &shortest);
+        print_solution("Best matching account:", &shortest, params.options, &goals)
+    } else {
+        Ok(())
+    };
+    params.check_stdout(printed, found_n);
+    params.exit(found_n);
+}
+
+#[cfg(test)]
+mod tests {
+    // importing names from outer (for mod tests) scope.
+    use super::*;
+    use pubkey_matcher::parse_patterns_file;
+    use std::env;
+    use std::sync::mpsc::sync_channel;
+
+    fn solution(key_byte: u8) -> Solution {
+        Solution {
+            kind: SolutionKind::Match,
+            key_material: [key_byte; 32],
+            key_type: GenerateKeyType::PrivateKey,
+            public_key: [key_byte; 32],
+            attempt: None,
+        }
+    }
+
+    #[test]
+    fn test_backpressure_from_str() {
+        assert_eq!("block".parse(), Ok(Backpressure::Block));
+        assert_eq!("drop".parse(), Ok(Backpressure::Drop));
+        assert!("".parse::<Backpressure>().is_err());
+        assert!("Block".parse::<Backpressure>().is_err());
+    }
+
+    #[test]
+    fn test_solution_json() {
+        let json = solution_json(&solution(1), &[]);
+        assert!(json.starts_with("{\"address\":\""));
+        assert!(json.contains(&format!("\"address\":\"{}L\"", solution(1).address())));
+        assert!(json.contains(&format!("\"addressNumeric\":\"{}\"", solution(1).address())));
+        assert!(json.contains(&format!("\"publicKey\":\"{}\"", "01".repeat(32))));
+        assert!(json.contains(&format!("\"privateKey\":\"{}\"", "01".repeat(64))));
+        assert!(!json.contains("attempt"));
+
+        let json = solution_json(
+            &Solution {
+                attempt: Some(1234),
+                ..solution(1)
+            },
+            &[],
+        );
+        assert!(json.ends_with("\",\"attempt\":1234}"));
+        assert!(!json.contains("goals"));
+
+        let goals = parse_patterns_file("len<=20\n\nsuffix:7\n", 10).unwrap();
+        let json = solution_json(&solution(1), &[&goals[0], &goals[1]]);
+        assert!(json.ends_with("\",\"goals\":[1,3]}"));
+    }
+
+    #[test]
+    fn test_lisk_uri() {
+        assert_eq!(lisk_uri(1234), "lisk:1234L");
+        assert_eq!(
+            lisk_uri(u64::MAX),
+            format!("lisk:{}", full_address(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn test_pubkey_only_line() {
+        let solution = Solution {
+            key_material: [0xaa; 32],
+            public_key: [0x01; 32],
+            ..solution(1)
+        };
+        let line = pubkey_only_line(&solution);
+        assert_eq!(line, format!("{},{}", "01".repeat(32), solution.address()));
+        assert!(!line.contains("AA"));
+        assert!(!line.contains(&solution.private_key_string()));
+    }
+
+    #[test]
+    fn test_solution_json_schema_in_sync() {
+        let json = solution_json(&solution(1), &[]);
+        let properties = ["address", "addressNumeric", "privateKey", "publicKey"];
+        for property in properties.iter() {
+            assert!(json.contains(&format!("\"{}\":", property)));
+            assert!(SOLUTION_JSON_SCHEMA.contains(&format!("\"{}\": {{", property)));
+        }
+        assert_eq!(json.matches("\":\"").count(), properties.len());
+        assert!(SOLUTION_JSON_SCHEMA.contains("\"attempt\": {"));
+        assert!(SOLUTION_JSON_SCHEMA.contains("\"goals\": {"));
+    }
+
+    #[test]
+    fn test_write_solution_file() {
+        let dir = env::temp_dir().join(format!("lisk-vanity-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{}L.json", solution(2).address()));
+
+        write_solution_file(&dir, &solution(2), &[], false).unwrap();
+        let written = fs::read_to_string(path).unwrap();
+        assert_eq!(written, format!("{}\n", solution_json(&solution(2), &[])));
+        // Existing files are only overwritten with force
+        assert!(write_solution_file(&dir, &solution(2), &[], false).is_err());
+        write_solution_file(&dir, &solution(2), &[], true).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    fn json_lines_params(writer: UnixStream) -> OutputParams {
+        OutputParams {
+            options: OutputOptions {
+                simple_output: false,
+                show_pubkey: false,
+                show_entropy: false,
+                recipe: false,
+                pubkey_only: false,
+                display_base: None,
+                uri: false,
+            },
+            output_progress: false,
+            limit: 0,
+            grace: None,
+            output_dir: None,
+            force: false,
+            attempts: None,
+            json_lines: Some(Box::new(writer)),
+            summary_every: None,
+            closest_at_end: false,
+            sorted: None,
+            start_time: Instant::now(),
+            threads: 1,
+            found_banner: true,
+            events: Events::disabled(),
+            tiers: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            state_file: None,
+            goals: Vec::new(),
+        }
+    }
+
+    #[cfg(unix)]
+    fn output_json_lines(
+        solutions: &[Solution],
+        sort: bool,
+        goals: Vec<PatternGoal>,
+    ) -> Vec<String> {
+        use std::io::{BufRead, BufReader};
+
+        let (writer, reader) = UnixStream::pair().unwrap();
+        let mut params = json_lines_params(writer);
+        params.goals = goals;
+        if sort {
+            params.sorted = Some(Vec::new());
+        }
+        let (sender, receiver) = sync_channel(solutions.len());
+        for &solution in solutions {
+            sender.send(solution).unwrap();
+        }
+        drop(sender);
+        assert_eq!(output_solutions(receiver, params).found, solutions.len());
+        BufReader::new(reader).lines().map(|l| l.unwrap()).collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_solutions_json_lines() {
+        assert_eq!(
+            output_json_lines(&[solution(3), solution(4)], false, Vec::new()),
+            vec![
+                solution_json(&solution(3), &[]),
+                solution_json(&solution(4), &[])
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_solutions_json_lines_goals() {
+        let content = "len<=20\nlen<=1\n";
+        let goals = parse_patterns_file(content, 10).unwrap();
+        assert!(!goals[1].matcher.matches_address(solution(3).address()));
+        assert_eq!(
+            output_json_lines(
+                &[solution(3)],
+                false,
+                parse_patterns_file(content, 10).unwrap()
+            ),
+            vec![solution_json(&solution(3), &[&goals[0]])]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_solutions_sorted() {
+        let solutions = [solution(3), solution(4), solution(5), solution(6)];
+        let mut expected = solutions.to_vec();
+        expected.sort_by_key(|solution| solution.address());
+        assert!(expected
+            .iter()
+            .zip(solutions.iter())
+            .any(|(a, b)| a.address() != b.address()));
+        assert_eq!(
+            output_json_lines(&solutions, true, Vec::new()),
+            expected
+                .iter()
+                .map(|solution| solution_json(solution, &[]))
+                .collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn test_time_summary() {
+        let second = Duration::from_secs(1);
+        assert_eq!(
+            time_summary(second * 10, Some(second * 30), 4),
+            "Wall-clock time 10.0s, CPU time 30.0s (75% parallel efficiency with 4 threads)"
+        );
+        assert_eq!(time_summary(second * 10, None, 4), "Wall-clock time 10.0s");
+    }
+
+    #[test]
+    fn test_difficulty_tiers() {
+        let mut tiers = DifficultyTiers::new();
+        for &address in &[123456789, 12345678, 987654321, 1] {
+            tiers.record(address);
+        }
+        assert_eq!(
+            tiers.report(),
+            "Matches by difficulty:\n  1 digits: 1 found\n    1L\n  8 digits: 1 found\n    12345678L\n  9 digits: 2 found\n    123456789L\n    987654321L"
+        );
+        assert_eq!(DifficultyTiers::new().report(), "Matches by difficulty:");
+    }
+
+    #[test]
+    fn test_find_stats() {
+        let mut stats = FindStats::new();
+        stats.record(1 << 63);
+        stats.record(1 << 61);
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.shortest, Some(1 << 61));
+        // (2 + 8) / 2
+        assert_eq!(
+            stats.summary(Duration::from_secs(4)),
+            format!(
+                "Summary: 2 matches, average difficulty 5, shortest {}L, 0.500 matches/s",
+                1u64 << 61
+            )
+        );
+
+        let mut stats = FindStats::new();
+        stats.record(0);
+        assert_eq!(
+            stats.summary(Duration::from_secs(0)),
+            "Summary: 1 matches, average difficulty 18446744073709551616, shortest 0L, 0.000 matches/s"
+        );
+    }
+
+    #[test]
+    fn test_send_solution_drops_when_full() {
+        let (sender, receiver) = sync_channel(2);
+        for i in 0..5 {
+            send_solution(&sender, Backpressure::Drop, solution(i));
+        }
+        drop(sender);
+        let received: Vec<Solution> = receiver.iter().collect();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].key_material, [0u8; 32]);
+        assert_eq!(received[1].key_material, [1u8; 32]);
+    }
+}
@@ -1,7 +1,17 @@
+use std::str::FromStr;
+
 use num_bigint::BigInt;
-use num_traits::pow;
+use num_traits::{pow, FromPrimitive};
 
 use derivation::pubkey_to_address;
+use format::group_thousands;
+use regex::Regex;
+
+// longest address: 18446744073709551615 (20 chars)
+pub const MAX_ADDRESS_LENGTH: usize = 20;
+
+// 2^64, the number of possible addresses
+const ADDRESS_SPACE: u128 = 1 << 64;
 
 // largest valid address
 pub fn max_address(max_len: usize) -> u64 {
@@ -12,39 +22,1504 @@ pub fn max_address(max_len: usize) -> u64 {
     }
 }
 
+// number of decimal digits of an address
+pub fn address_length(address: u64) -> usize {
+    let mut length = 1;
+    let mut rest = address / 10;
+    while rest != 0 {
+        length += 1;
+        rest /= 10;
+    }
+    length
+}
+
+// The address in `base` (2 to 36) with lowercase letters (see --base)
+pub fn render_in_base(mut address: u64, base: u32) -> String {
+    let mut digits = Vec::new();
+    loop {
+        digits.push(std::char::from_digit((address % base as u64) as u32, base).unwrap());
+        address /= base as u64;
+        if address == 0 {
+            break;
+        }
+    }
+    digits.iter().rev().collect()
+}
+
+// Number of digits of the longest address in `base`
+fn max_length_in_base(base: u32) -> usize {
+    render_in_base(u64::MAX, base).len()
+}
+
+// Number of addresses starting with `prefix` in `base`
+fn prefix_count(prefix: &str, base: u32) -> Option<u128> {
+    let k = prefix.len() as u32;
+    let value = u128::from_str_radix(prefix, base).ok()?;
+    if prefix.starts_with('0') {
+        // "0" is the only address with a leading zero
+        return Some(if prefix == "0" { 1 } else { 0 });
+    }
+    // For every address length L >= k, the matches are the interval
+    // [prefix * base^(L-k), (prefix + 1) * base^(L-k))
+    let mut count = 0;
+    for shift in 0..=(max_length_in_base(base) as u32).saturating_sub(k) {
+        let factor = (base as u128).pow(shift);
+        let start = value * factor;
+        let end = ((value + 1) * factor).min(ADDRESS_SPACE);
+        if start < end {
+            count += end - start;
+        }
+    }
+    Some(count)
+}
+
+// Number of addresses ending with `suffix` in `base`
+fn suffix_count(suffix: &str, base: u32) -> Option<u128> {
+    let k = suffix.len() as u32;
+    let value = u128::from_str_radix(suffix, base).ok()?;
+    let modulus = (base as u128).checked_pow(k)?;
+    if value >= ADDRESS_SPACE {
+        return Some(0);
+    }
+    // all x < 2^64 with x ≡ value (mod base^k)
+    let mut count = (ADDRESS_SPACE - 1 - value) / modulus + 1;
+    if suffix.starts_with('0') {
+        // `value` itself is rendered without the leading zeros
+        count -= 1;
+    }
+    Some(count)
+}
+
+// Number of `len` digit addresses starting with `prefix`
+fn prefix_count_at_length(prefix: &str, len: usize) -> Option<u128> {
+    let value: u128 = prefix.parse().ok()?;
+    if prefix.starts_with('0') {
+        return Some(if prefix == "0" && len == 1 { 1 } else { 0 });
+    }
+    if len < prefix.len() {
+        return Some(0);
+    }
+    // [prefix * 10^(len-k), (prefix + 1) * 10^(len-k)) clipped to the `len` digit addresses
+    let factor = 10u128.pow((len - prefix.len()) as u32);
+    let (_, end) = length_interval(len);
+    Some(
+        ((value + 1) * factor)
+            .min(end)
+            .saturating_sub(value * factor),
+    )
+}
+
+// Number of `len` digit addresses ending with `suffix`
+fn suffix_count_at_length(suffix: &str, len: usize) -> Option<u128> {
+    let value: u128 = suffix.parse().ok()?;
+    if len < suffix.len() {
+        return Some(0);
+    }
+    let modulus = 10u128.pow(suffix.len() as u32);
+    // all x < bound with x ≡ value (mod 10^k)
+    let below = |bound: u128| {
+        if bound > value {
+            (bound - 1 - value) / modulus + 1
+        } else {
+            0
+        }
+    };
+    let (start, end) = length_interval(len);
+    Some(below(end) - below(start))
+}
+
+// Luhn checksum (as used for credit card numbers) over the decimal digits
+fn luhn_valid(digits: &str) -> bool {
+    let checksum = digits
+        .bytes()
+        .rev()
+        .enumerate()
+        .map(|(i, byte)| {
+            let digit = (byte - b'0') as u32;
+            if i % 2 == 1 {
+                let doubled = digit * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                digit
+            }
+        })
+        .fold(0, |checksum, digit| (checksum + digit) % 10);
+    checksum == 0
+}
+
+// Odd number of digits reading the same in reverse order around a free center digit,
+// e.g. "12321"
+fn mirrored(digits: &str) -> bool {
+    let digits = digits.as_bytes();
+    digits.len() % 2 == 1 && digits.iter().eq(digits.iter().rev())
+}
+
+// For an odd length L the first (L + 1) / 2 digits determine the address, where the first
+// one is not 0 unless L = 1. Even lengths never match.
+fn mirrored_count_at_length(len: usize) -> u128 {
+    match len {
+        1 => 10,
+        _ if len % 2 == 1 => 9 * 10u128.pow((len as u32 - 1) / 2),
+        _ => 0,
+    }
+}
+
+// This sums up to 10^10 addresses, i.e. one in ~1.8 billion
+fn mirrored_match_count() -> u128 {
+    (1..=MAX_ADDRESS_LENGTH).map(mirrored_count_at_length).sum()
+}
+
+// Fraction of all addresses covered by the given number of attempts. Since addresses are
+// hashes, this assumes that every attempt hits a new address, which holds while the
+// fraction is small.
+pub fn address_space_coverage(attempts: u64) -> f64 {
+    attempts as f64 / ADDRESS_SPACE as f64
+}
+
+fn big_int_from_u128(value: u128) -> BigInt {
+    (BigInt::from((value >> 64) as u64) << 64) + BigInt::from(value as u64)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    AtLeast,
+    AtMost,
+    Exactly,
+}
+
+impl Comparison {
+    fn holds(self, value: usize, bound: usize) -> bool {
+        match self {
+            Comparison::AtLeast => value >= bound,
+            Comparison::AtMost => value <= bound,
+            Comparison::Exactly => value == bound,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Comparison::AtLeast => "at least",
+            Comparison::AtMost => "at most",
+            Comparison::Exactly => "exactly",
+        }
+    }
+}
+
+// [start, end) of the addresses with `len` digits, 1 <= len <= MAX_ADDRESS_LENGTH
+fn length_interval(len: usize) -> (u128, u128) {
+    let start = if len > 1 {
+        10u128.pow(len as u32 - 1)
+    } else {
+        0
+    };
+    (start, 10u128.pow(len as u32).min(ADDRESS_SPACE))
+}
+
+// Number of addresses with `min_len` to `max_len` digits, i.e. in
+// [10^(min_len-1), 10^max_len), where 1-digit addresses include 0
+fn length_range_count(min_len: usize, max_len: usize) -> u128 {
+    if min_len == 0 || max_len > MAX_ADDRESS_LENGTH || min_len > max_len {
+        return 0;
+    }
+    let shorter = if min_len > 1 {
+        max_address(min_len - 1) as u128 + 1
+    } else {
+        0
+    };
+    max_address(max_len) as u128 + 1 - shorter
+}
+
+// Addresses with `target` digits give or take `tolerance` (see --target-length). The range
+// is clipped to the possible address lengths.
+pub fn length_band(target: usize, tolerance: usize) -> Condition {
+    Condition::LengthRange(
+        target.saturating_sub(tolerance).max(1),
+        (target + tolerance).min(MAX_ADDRESS_LENGTH),
+    )
+}
+
+// Number of addresses `sampled_probability` tries
+const PROBABILITY_SAMPLES: u64 = 100_000;
+
+// The share of a fixed sequence of pseudo random addresses matching `condition`, for
+// conditions without a model. A condition without any sampled match counts as one match, so
+// its probability is only an upper bound (see `PubkeyMatcher::unsampled_conditions`).
+fn sampled_probability(condition: &Condition) -> f64 {
+    sampled_hits(condition).max(1) as f64 / PROBABILITY_SAMPLES as f64
+}
+
+fn sampled_hits(condition: &Condition) -> u64 {
+    // SplitMix64, good enough to spread the addresses over all lengths
+    let mut state: u64 = 0;
+    (0..PROBABILITY_SAMPLES)
+        .filter(|_| {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut address = state;
+            address = (address ^ (address >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            address = (address ^ (address >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            address ^= address >> 31;
+            condition.matches(address, &address.to_string())
+        })
+        .count() as u64
+}
+
+// A regular expression matched against the address with its "L" suffix, e.g. "^1\d{3}L$"
+// (see --regex and `regex::Regex`)
+pub fn regex_condition(source: &str) -> Result<Condition, String> {
+    Ok(Condition::Regex(Regex::new(source)?))
+}
+
+// A digest with at least `bits` leading zero bits (see --hash-leading-zeros). Only the first
+// eight bytes of the digest are kept in the address, so at most 64 bits can be checked.
+pub fn hash_leading_zeros(bits: u32) -> Result<Condition, String> {
+    if bits == 0 || bits > 64 {
+        return Err(format!(
+            "Leading zero bits must be between 1 and 64, got {}",
+            bits
+        ));
+    }
+    Ok(Condition::HashLeadingZeros(bits))
+}
+
+// Probability that `k` of `n` independent events with probability `p` happen
+fn binomial(n: usize, k: usize, p: f64) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    let mut coefficient = 1.0;
+    for i in 0..k {
+        coefficient = coefficient * (n - i) as f64 / (i + 1) as f64;
+    }
+    coefficient * p.powi(k as i32) * (1.0 - p).powi((n - k) as i32)
+}
+
+// Conditions on the address grouped by thousands separators, e.g. "123,456,123"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Grouping {
+    // All groups are equal, e.g. "123,123,123"
+    EqualGroups,
+    // The groups read the same in reverse order, e.g. "123,456,123"
+    Palindrome,
+}
+
+impl Grouping {
+    fn matches(self, digits: &str) -> bool {
+        let grouped = group_thousands(digits);
+        let groups: Vec<&str> = grouped.split(',').collect();
+        groups.len() > 1
+            && match self {
+                Grouping::EqualGroups => groups.iter().all(|&group| group == groups[0]),
+                Grouping::Palindrome => groups.iter().eq(groups.iter().rev()),
+            }
+    }
+
+    // Both groupings require a complete leading group, i.e. 2 to 6 groups of 3 digits
+    fn match_count(self) -> u128 {
+        (2..=MAX_ADDRESS_LENGTH / 3)
+            .map(|groups| match self {
+                Grouping::EqualGroups => 900,
+                // the first half of the groups is free, the leading one without a leading zero
+                Grouping::Palindrome => 900 * 1000u128.pow((groups as u32).div_ceil(2) - 1),
+            })
+            .sum()
+    }
+}
+
+impl FromStr for Grouping {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Grouping, String> {
+        match input {
+            "equal" => Ok(Grouping::EqualGroups),
+            "palindrome" => Ok(Grouping::Palindrome),
+            _ => Err(format!(
+                "Unknown grouping \"{}\". Expected \"equal\" or \"palindrome\".",
+                input
+            )),
+        }
+    }
+}
+
+// Direction of strictly monotonic digits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Monotonic {
+    // e.g. "1357"
+    Increasing,
+    // e.g. "975"
+    Decreasing,
+}
+
+impl Monotonic {
+    fn matches(self, digits: &str) -> bool {
+        let digits = digits.as_bytes();
+        digits.windows(2).all(|pair| match self {
+            Monotonic::Increasing => pair[0] < pair[1],
+            Monotonic::Decreasing => pair[0] > pair[1],
+        })
+    }
+
+    // Every set of distinct digits gives exactly one address
+    fn match_count(self) -> u128 {
+        match self {
+            // non-empty subsets of 1-9, plus "0"
+            Monotonic::Increasing => (1 << 9) - 1 + 1,
+            // non-empty subsets of 0-9, where {0} is "0"
+            Monotonic::Decreasing => (1 << 10) - 1,
+        }
+    }
+
+    fn max_length(self) -> usize {
+        match self {
+            Monotonic::Increasing => 9,
+            Monotonic::Decreasing => 10,
+        }
+    }
+}
+
+impl FromStr for Monotonic {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Monotonic, String> {
+        match input {
+            "inc" => Ok(Monotonic::Increasing),
+            "dec" => Ok(Monotonic::Decreasing),
+            _ => Err(format!(
+                "Unknown direction \"{}\". Expected \"inc\" or \"dec\".",
+                input
+            )),
+        }
+    }
+}
+
+// A single condition on the decimal representation of an address
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    MaxLength(usize),
+    ExactLength(usize),
+    // Minimum and maximum length, both inclusive (see --target-length)
+    LengthRange(usize, usize),
+    Prefix(String),
+    Suffix(String),
+    Contains(String),
+    // The digits do not appear anywhere in the address (see --unlucky)
+    Excludes(String),
+    Luhn,
+    // e.g. "12321" (see `mirrored`)
+    Mirror,
+    // A prefix, suffix or contains condition on the address rendered in another base
+    // than 10 (see --base)
+    InBase(u32, Box<Condition>),
+    // The number of occurrences of a digit
+    DigitCount(u8, Comparison, usize),
+    Grouped(Grouping),
+    Monotonic(Monotonic),
+    // A regular expression on the address with its "L" suffix (see --regex)
+    Regex(Regex),
+    // The minimum number of leading zero bits of the SHA-256 digest of the public key,
+    // independent of the rendered address (see --hash-leading-zeros)
+    HashLeadingZeros(u32),
+}
+
+impl Condition {
+    fn describe(&self) -> String {
+        match self {
+            Condition::MaxLength(max_len) => format!("max length {}", max_len),
+            Condition::ExactLength(len) => format!("length {}", len),
+            Condition::LengthRange(min_len, max_len) => {
+                format!("length {} to {}", min_len, max_len)
+            }
+            Condition::Prefix(prefix) => format!("prefix {}", prefix),
+            Condition::Suffix(suffix) => format!("suffix {}", suffix),
+            Condition::Contains(infix) => format!("contains {}", infix),
+            Condition::Excludes(digits) => format!("without {}", digits),
+            Condition::Luhn => "Luhn checksum".to_string(),
+            Condition::Mirror => "mirrored digits".to_string(),
+            Condition::InBase(base, condition) => {
+                format!("{} in base {}", condition.describe(), base)
+            }
+            Condition::DigitCount(digit, comparison, bound) => {
+                format!("{} {} times digit {}", comparison.describe(), bound, digit)
+            }
+            Condition::Grouped(Grouping::EqualGroups) => "equal digit groups".to_string(),
+            Condition::Grouped(Grouping::Palindrome) => "palindromic digit groups".to_string(),
+            Condition::Monotonic(Monotonic::Increasing) => "increasing digits".to_string(),
+            Condition::Monotonic(Monotonic::Decreasing) => "decreasing digits".to_string(),
+            Condition::Regex(regex) => format!("regex {}", regex.as_str()),
+            Condition::HashLeadingZeros(bits) => format!("{} leading zero bits of the hash", bits),
+        }
+    }
+
+    fn needs_digits(&self) -> bool {
+        !matches!(
+            self,
+            Condition::MaxLength(_)
+                | Condition::ExactLength(_)
+                | Condition::LengthRange(..)
+                | Condition::HashLeadingZeros(_)
+        )
+    }
+
+    fn matches(&self, address: u64, digits: &str) -> bool {
+        match self {
+            // Example max_len = 15
+            // Short address: 999999999999999 (15 chars)
+            // Strict upper bound = 10^15 = 1000000000000000
+            Condition::MaxLength(max_len) => address <= max_address(*max_len),
+            Condition::ExactLength(len) => address_length(address) == *len,
+            Condition::LengthRange(min_len, max_len) => {
+                (*min_len..=*max_len).contains(&address_length(address))
+            }
+            Condition::Prefix(prefix) => digits.starts_with(prefix.as_str()),
+            Condition::Suffix(suffix) => digits.ends_with(suffix.as_str()),
+            Condition::Contains(infix) => digits.contains(infix.as_str()),
+            Condition::Excludes(excluded) => !digits.contains(excluded.as_str()),
+            Condition::Luhn => luhn_valid(digits),
+            Condition::Mirror => mirrored(digits),
+            Condition::InBase(base, condition) => {
+                condition.matches(address, &render_in_base(address, *base))
+            }
+            Condition::DigitCount(digit, comparison, bound) => {
+                let count = digits.bytes().filter(|&byte| byte == b'0' + digit).count();
+                comparison.holds(count, *bound)
+            }
+            Condition::Grouped(grouping) => grouping.matches(digits),
+            Condition::Monotonic(direction) => direction.matches(digits),
+            Condition::Regex(regex) => {
+                let mut text = [0u8; MAX_ADDRESS_LENGTH + 1];
+                text[..digits.len()].copy_from_slice(digits.as_bytes());
+                text[digits.len()] = b'L';
+                regex.is_match(&text[..digits.len() + 1])
+            }
+            // The address holds the first eight bytes of the digest in little endian order
+            Condition::HashLeadingZeros(bits) => address.swap_bytes().leading_zeros() >= *bits,
+        }
+    }
+
+    // The exact number of matching addresses, if this can be calculated
+    fn match_count(&self) -> Option<u128> {
+        match self {
+            Condition::MaxLength(max_len) => Some(max_address(*max_len) as u128 + 1),
+            Condition::ExactLength(len) => Some(length_range_count(*len, *len)),
+            Condition::LengthRange(min_len, max_len) => {
+                Some(length_range_count(*min_len, *max_len))
+            }
+            Condition::Prefix(prefix) => prefix_count(prefix, 10),
+            Condition::Suffix(suffix) => suffix_count(suffix, 10),
+            Condition::InBase(base, condition) => match **condition {
+                Condition::Prefix(ref prefix) => prefix_count(prefix, *base),
+                Condition::Suffix(ref suffix) => suffix_count(suffix, *base),
+                _ => None,
+            },
+            Condition::Grouped(grouping) => Some(grouping.match_count()),
+            Condition::Monotonic(direction) => Some(direction.match_count()),
+            Condition::Mirror => Some(mirrored_match_count()),
+            Condition::HashLeadingZeros(bits) => Some(ADDRESS_SPACE >> bits),
+            Condition::Contains(_)
+            | Condition::Excludes(_)
+            | Condition::Luhn
+            | Condition::DigitCount(..)
+            | Condition::Regex(_) => None,
+        }
+    }
+
+    // The exact number of matching addresses of every length from 1 to MAX_ADDRESS_LENGTH, if
+    // this can be calculated. Unlike `match_count`, this allows combining conditions that
+    // depend on the length, e.g. "len==3 & prefix:123" only matches 123.
+    fn length_match_counts(&self) -> Option<Vec<u128>> {
+        let count_at_length = |len: usize| match self {
+            Condition::MaxLength(max_len) if len > *max_len => Some(0),
+            Condition::ExactLength(exact_len) if len != *exact_len => Some(0),
+            Condition::LengthRange(min_len, max_len) if len < *min_len || len > *max_len => Some(0),
+            Condition::MaxLength(_) | Condition::ExactLength(_) | Condition::LengthRange(..) => {
+                Some(length_range_count(len, len))
+            }
+            Condition::Prefix(prefix) => prefix_count_at_length(prefix, len),
+            Condition::Suffix(suffix) => suffix_count_at_length(suffix, len),
+            Condition::Mirror => Some(mirrored_count_at_length(len)),
+            _ => None,
+        };
+        (1..=MAX_ADDRESS_LENGTH).map(count_at_length).collect()
+    }
+
+    // False if no address can match. True does not guarantee a match.
+    fn can_match(&self) -> bool {
+        match self {
+            Condition::DigitCount(_, Comparison::AtLeast, bound)
+            | Condition::DigitCount(_, Comparison::Exactly, bound) => *bound <= MAX_ADDRESS_LENGTH,
+            _ => self.match_count() != Some(0),
+        }
+    }
+
+    // Bounds for the address length implied by this condition
+    fn length_bounds(&self) -> (usize, usize) {
+        match self {
+            Condition::MaxLength(max_len) => (1, *max_len),
+            Condition::ExactLength(len) => (*len, *len),
+            Condition::LengthRange(min_len, max_len) => (*min_len, *max_len),
+            Condition::Prefix(digits) | Condition::Suffix(digits) | Condition::Contains(digits) => {
+                (digits.len(), MAX_ADDRESS_LENGTH)
+            }
+            Condition::DigitCount(_, Comparison::AtLeast, bound)
+            | Condition::DigitCount(_, Comparison::Exactly, bound) => {
+                ((*bound).max(1), MAX_ADDRESS_LENGTH)
+            }
+            Condition::Luhn
+            | Condition::Excludes(_)
+            | Condition::Regex(_)
+            | Condition::DigitCount(_, Comparison::AtMost, _)
+            | Condition::HashLeadingZeros(_) => (1, MAX_ADDRESS_LENGTH),
+            Condition::Grouped(_) => (6, MAX_ADDRESS_LENGTH / 3 * 3),
+            Condition::Monotonic(direction) => (1, direction.max_length()),
+            // 20 digits is even
+            Condition::Mirror => (1, MAX_ADDRESS_LENGTH - 1),
+            // the decimal length is not bounded by the digits in another base
+            Condition::InBase(..) => (1, MAX_ADDRESS_LENGTH),
+        }
+    }
+
+    // The condition made easier by one level, e.g. one fixed digit less (see --preview)
+    fn relaxed(&self) -> Condition {
+        match self {
+            Condition::MaxLength(max_len) => {
+                Condition::MaxLength((max_len + 1).min(MAX_ADDRESS_LENGTH))
+            }
+            // A single fixed digit relaxes to any address
+            Condition::Prefix(digits) | Condition::Suffix(digits) | Condition::Contains(digits)
+                if digits.len() <= 1 =>
+            {
+                Condition::MaxLength(MAX_ADDRESS_LENGTH)
+            }
+            Condition::Prefix(digits) => Condition::Prefix(digits[..digits.len() - 1].to_string()),
+            Condition::Suffix(digits) => Condition::Suffix(digits[1..].to_string()),
+            Condition::Contains(digits) => {
+                Condition::Contains(digits[..digits.len() - 1].to_string())
+            }
+            Condition::DigitCount(digit, Comparison::AtLeast, bound) => {
+                Condition::DigitCount(*digit, Comparison::AtLeast, bound.saturating_sub(1))
+            }
+            Condition::DigitCount(digit, Comparison::AtMost, bound) => {
+                Condition::DigitCount(*digit, Comparison::AtMost, bound + 1)
+            }
+            Condition::HashLeadingZeros(bits) if *bits > 1 => Condition::HashLeadingZeros(bits - 1),
+            Condition::InBase(base, condition) => match condition.relaxed() {
+                relaxed @ Condition::Prefix(_)
+                | relaxed @ Condition::Suffix(_)
+                | relaxed @ Condition::Contains(_) => Condition::InBase(*base, Box::new(relaxed)),
+                relaxed => relaxed,
+            },
+            Condition::ExactLength(_)
+            | Condition::LengthRange(..)
+            | Condition::Excludes(_)
+            | Condition::Regex(_)
+            | Condition::Luhn
+            | Condition::Mirror
+            | Condition::DigitCount(..)
+            | Condition::Grouped(_)
+            | Condition::Monotonic(_)
+            | Condition::HashLeadingZeros(_) => self.clone(),
+        }
+    }
+
+    fn probability(&self) -> f64 {
+        match self.match_count() {
+            Some(count) => count as f64 / ADDRESS_SPACE as f64,
+            None => match self {
+                Condition::Contains(infix) => {
+                    // Approximation: the infix can start at every position of an address
+                    // with ~19.4 digits on average, each position matching with 10^-k.
+                    let k = infix.len() as f64;
+                    let positions = (19.4 - k + 1.0).max(0.0);
+                    (positions * 10f64.powf(-k)).min(1.0)
+                }
+                // Same approximation, with none of the positions matching
+                Condition::Excludes(digits) => {
+                    let k = digits.len() as f64;
+                    let positions = (19.4 - k + 1.0).max(0.0);
+                    (1.0 - 10f64.powf(-k)).powf(positions)
+                }
+                // As for decimal addresses, with the average length about half a digit
+                // below the longest address
+                Condition::InBase(base, condition) => match **condition {
+                    Condition::Contains(ref infix) => {
+                        let k = infix.len() as f64;
+                        let positions = (max_length_in_base(*base) as f64 - 0.5 - k + 1.0).max(0.0);
+                        (positions * (*base as f64).powf(-k)).min(1.0)
+                    }
+                    _ => unreachable!(),
+                },
+                // The check digit matches in one of 10 cases
+                Condition::Luhn => 0.1,
+                Condition::Regex(_) => sampled_probability(self),
+                // Approximation: 19 independent digits, each being `digit` with 1/10
+                Condition::DigitCount(_, comparison, bound) => (0..=19)
+                    .filter(|&count| comparison.holds(count, *bound))
+                    .map(|count| binomial(19, count, 0.1))
+                    .sum(),
+                _ => unreachable!(),
+            },
+        }
+    }
+}
+
+// The keywords starting a condition, in the order the parser tries them
+pub const CONDITION_KEYWORDS: &[&str] = &[
+    "len<=",
+    "len==",
+    "prefix:",
+    "suffix:",
+    "contains:",
+    "excludes:",
+    "luhn",
+    "mirror",
+    "count:",
+    "groups:",
+    "monotonic:",
+    "hashzeros:",
+];
+
+// Grammar of the pattern language parsed by `PubkeyMatcher::from_str`:
+//
+//   pattern   := all ( "|" all )*
+//   all       := term ( "&" term )*
+//   term      := condition | "(" pattern ")"
+//   condition := "len<=" NUMBER | "len==" NUMBER | "prefix:" DIGITS | "suffix:" DIGITS | "contains:" DIGITS
+//              | "excludes:" DIGITS | "luhn" | "mirror" | "count:" DIGIT ( ">=" | "<=" | "=" ) NUMBER
+//              | "groups:" ( "equal" | "palindrome" ) | "monotonic:" ( "inc" | "dec" )
+//              | "hashzeros:" NUMBER
+//
+// "&" binds stronger than "|". Whitespace between tokens is ignored.
+// Example: "len<=12 | suffix:777 & contains:8888"
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    Condition(Condition),
+    All(Vec<Pattern>),
+    Any(Vec<Pattern>),
+}
+
+impl Pattern {
+    // Combinations nested in other combinations are parenthesized, e.g.
+    // "suffix 7 or (prefix 1 and Luhn checksum)"
+    fn describe(&self) -> String {
+        let join = |patterns: &[Pattern], separator| {
+            patterns
+                .iter()
+                .map(|p| match p {
+                    Pattern::Condition(condition) => condition.describe(),
+                    _ => format!("({})", p.describe()),
+                })
+                .collect::<Vec<String>>()
+                .join(separator)
+        };
+        match self {
+            Pattern::Condition(condition) => condition.describe(),
+            Pattern::All(patterns) => join(patterns, " and "),
+            Pattern::Any(patterns) => join(patterns, " or "),
+        }
+    }
+
+    fn needs_digits(&self) -> bool {
+        match self {
+            Pattern::Condition(condition) => condition.needs_digits(),
+            Pattern::All(patterns) | Pattern::Any(patterns) => {
+                patterns.iter().any(|p| p.needs_digits())
+            }
+        }
+    }
+
+    // Descriptions of the conditions whose probability is only an upper bound since they never
+    // matched a sampled address
+    fn unsampled_conditions(&self) -> Vec<String> {
+        match self {
+            Pattern::Condition(condition @ Condition::Regex(_)) if sampled_hits(condition) == 0 => {
+                vec![condition.describe()]
+            }
+            Pattern::Condition(_) => Vec::new(),
+            Pattern::All(patterns) | Pattern::Any(patterns) => patterns
+                .iter()
+                .flat_map(|p| p.unsampled_conditions())
+                .collect(),
+        }
+    }
+
+    fn matches(&self, address: u64, digits: &str) -> bool {
+        match self {
+            Pattern::Condition(condition) => condition.matches(address, digits),
+            Pattern::All(patterns) => patterns.iter().all(|p| p.matches(address, digits)),
+            Pattern::Any(patterns) => patterns.iter().any(|p| p.matches(address, digits)),
+        }
+    }
+
+    // False if no address can match, e.g. "len<=3 & prefix:1234" or "len==12 & mirror".
+    // True does not guarantee a match since only the conditions directly combined with "&"
+    // are compared.
+    fn can_match(&self) -> bool {
+        match self {
+            Pattern::Condition(condition) => condition.can_match(),
+            Pattern::Any(patterns) => patterns.iter().any(|p| p.can_match()),
+            Pattern::All(patterns) => {
+                let mut min_len = 1;
+                let mut max_len = MAX_ADDRESS_LENGTH;
+                // Lengths with a match of every condition with per-length counts, e.g. only
+                // odd lengths for mirrored digits
+                let mut possible_lengths = [true; MAX_ADDRESS_LENGTH];
+                for pattern in patterns {
+                    if !pattern.can_match() {
+                        return false;
+                    }
+                    if let Pattern::Condition(condition) = pattern {
+                        let (min, max) = condition.length_bounds();
+                        min_len = min_len.max(min);
+                        max_len = max_len.min(max);
+                        if let Some(counts) = condition.length_match_counts() {
+                            for (possible, count) in possible_lengths.iter_mut().zip(counts) {
+                                *possible &= count > 0;
+                            }
+                        }
+                    }
+                }
+                min_len <= max_len && possible_lengths[min_len - 1..max_len].contains(&true)
+            }
+        }
+    }
+
+    fn relaxed(&self) -> Pattern {
+        match self {
+            Pattern::Condition(condition) => Pattern::Condition(condition.relaxed()),
+            Pattern::All(patterns) => Pattern::All(patterns.iter().map(|p| p.relaxed()).collect()),
+            Pattern::Any(patterns) => Pattern::Any(patterns.iter().map(|p| p.relaxed()).collect()),
+        }
+    }
+
+    // The exact number of matching addresses, if this can be calculated. Combinations are
+    // exact if every condition has per-length counts and at most one of them restricts the
+    // digits, e.g. "len<=10 & prefix:1234".
+    fn match_count(&self) -> Option<u128> {
+        match self {
+            Pattern::Condition(condition) => condition.match_count(),
+            Pattern::All(patterns) => {
+                let mut digit_conditions = 0;
+                let mut counts: Vec<u128> = (1..=MAX_ADDRESS_LENGTH)
+                    .map(|len| length_range_count(len, len))
+                    .collect();
+                for pattern in patterns {
+                    let condition = match pattern {
+                        Pattern::Condition(condition) => condition,
+                        _ => return None,
+                    };
+                    if condition.needs_digits() {
+                        digit_conditions += 1;
+                    }
+                    // Length conditions count all or none of the addresses of a length
+                    for (count, condition_count) in
+                        counts.iter_mut().zip(condition.length_match_counts()?)
+                    {
+                        *count = (*count).min(condition_count);
+                    }
+                }
+                if digit_conditions > 1 {
+                    return None;
+                }
+                Some(counts.iter().sum())
+            }
+            Pattern::Any(_) => None,
+        }
+    }
+
+    // Combines the conditions with per-length counts length by length, since they are
+    // correlated through the length, and assumes everything else to be independent, which is
+    // good enough for an estimate
+    fn probability(&self) -> f64 {
+        match self {
+            Pattern::Condition(condition) => condition.probability(),
+            Pattern::All(patterns) => {
+                if let Some(count) = self.match_count() {
+                    return count as f64 / ADDRESS_SPACE as f64;
+                }
+                // The share of the addresses of every length matching all conditions with
+                // per-length counts
+                let mut shares = vec![1.0; MAX_ADDRESS_LENGTH];
+                let mut independent = 1.0;
+                for pattern in patterns {
+                    let counts = match pattern {
+                        Pattern::Condition(condition) => condition.length_match_counts(),
+                        _ => None,
+                    };
+                    match counts {
+                        Some(counts) => {
+                            for (len, (share, count)) in (1..).zip(shares.iter_mut().zip(counts)) {
+                                *share *= count as f64 / length_range_count(len, len) as f64;
+                            }
+                        }
+                        None => independent *= pattern.probability(),
+                    }
+                }
+                let matching: f64 = (1..)
+                    .zip(shares)
+                    .map(|(len, share)| share * length_range_count(len, len) as f64)
+                    .sum();
+                matching / ADDRESS_SPACE as f64 * independent
+            }
+            Pattern::Any(patterns) => {
+                1.0 - patterns
+                    .iter()
+                    .map(|p| 1.0 - p.probability())
+                    .product::<f64>()
+            }
+        }
+    }
+}
+
+// A prefix, suffix or contains condition on the address rendered in `base`
+fn in_base(base: u32, condition: Condition) -> Condition {
+    if base == 10 {
+        condition
+    } else {
+        Condition::InBase(base, Box::new(condition))
+    }
+}
+
+struct PatternParser<'a> {
+    input: &'a str,
+    pos: usize,
+    // Base of the digits of prefix, suffix and contains conditions (see --base)
+    base: u32,
+}
+
+impl<'a> PatternParser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn digits(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(format!("Expected digits at position {}", self.pos));
+        }
+        if len > MAX_ADDRESS_LENGTH {
+            return Err(format!(
+                "Digits at position {} are longer than the longest address ({} digits)",
+                self.pos, MAX_ADDRESS_LENGTH
+            ));
+        }
+        self.pos += len;
+        Ok(rest[..len].to_string())
+    }
+
+    // A decimal number like a length, which may not fit `T` even with at most 20 digits
+    fn number<T: FromStr>(&mut self) -> Result<T, String> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let digits = self.digits()?;
+        digits
+            .parse()
+            .map_err(|_| format!("Number {} at position {} is too large", digits, start))
+    }
+
+    // Digits of a prefix, suffix or contains condition, lowercase in bases above 10
+    fn base_digits(&mut self) -> Result<String, String> {
+        let base = self.base;
+        if base == 10 {
+            return self.digits();
+        }
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest.find(|c: char| !c.is_digit(base)).unwrap_or(rest.len());
+        if len == 0 {
+            return Err(format!(
+                "Expected base {} digits at position {}",
+                base, self.pos
+            ));
+        }
+        if len > max_length_in_base(base) {
+            return Err(format!(
+                "Digits at position {} are longer than the longest address in base {} ({} digits)",
+                self.pos,
+                base,
+                max_length_in_base(base)
+            ));
+        }
+        self.pos += len;
+        Ok(rest[..len].to_ascii_lowercase())
+    }
+
+    fn in_base(&self, condition: Condition) -> Condition {
+        in_base(self.base, condition)
+    }
+
+    // A lowercase word like "equal", parsed into `T`
+    fn word<T: FromStr<Err = String>>(&mut self) -> Result<T, String> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !c.is_ascii_lowercase())
+            .unwrap_or(rest.len());
+        let value = rest[..len]
+            .parse()
+            .map_err(|error| format!("{} at position {}", error, self.pos))?;
+        self.pos += len;
+        Ok(value)
+    }
+
+    // A digit count rule like "7>=3"
+    fn digit_count(&mut self) -> Result<Condition, String> {
+        self.skip_whitespace();
+        let digit = match self.rest().bytes().next() {
+            Some(byte) if byte.is_ascii_digit() => byte - b'0',
+            _ => return Err(format!("Expected a digit at position {}", self.pos)),
+        };
+        self.pos += 1;
+        let comparison = if self.eat(">=") {
+            Comparison::AtLeast
+        } else if self.eat("<=") {
+            Comparison::AtMost
+        } else if self.eat("=") {
+            Comparison::Exactly
+        } else {
+            return Err(format!(
+                "Expected one of \">=\", \"<=\" or \"=\" at position {}",
+                self.pos
+            ));
+        };
+        let bound = self.number()?;
+        Ok(Condition::DigitCount(digit, comparison, bound))
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, String> {
+        let mut patterns = vec![self.all()?];
+        while self.eat("|") {
+            patterns.push(self.all()?);
+        }
+        Ok(if patterns.len() == 1 {
+            patterns.pop().unwrap()
+        } else {
+            Pattern::Any(patterns)
+        })
+    }
+
+    fn all(&mut self) -> Result<Pattern, String> {
+        let mut patterns = vec![self.term()?];
+        while self.eat("&") {
+            patterns.push(self.term()?);
+        }
+        Ok(if patterns.len() == 1 {
+            patterns.pop().unwrap()
+        } else {
+            Pattern::All(patterns)
+        })
+    }
+
+    fn term(&mut self) -> Result<Pattern, String> {
+        if self.eat("(") {
+            let pattern = self.pattern()?;
+            if !self.eat(")") {
+                return Err(format!("Expected \")\" at position {}", self.pos));
+            }
+            return Ok(pattern);
+        }
+
+        let condition = if self.eat("len<=") {
+            let max_len: usize = self.number()?;
+            if max_len == 0 {
+                return Err("Length must be at least 1".to_string());
+            }
+            Condition::MaxLength(max_len)
+        } else if self.eat("len==") {
+            let len: usize = self.number()?;
+            if len == 0 || len > MAX_ADDRESS_LENGTH {
+                return Err(format!(
+                    "Exact length must be between 1 and {}",
+                    MAX_ADDRESS_LENGTH
+                ));
+            }
+            Condition::ExactLength(len)
+        } else if self.eat("prefix:") {
+            let prefix = self.base_digits()?;
+            if prefix.len() > 1 && prefix.starts_with('0') {
+                return Err(format!(
+                    "Prefix \"{}\" can never match since addresses have no leading zeros",
+                    prefix
+                ));
+            }
+            self.in_base(Condition::Prefix(prefix))
+        } else if self.eat("suffix:") {
+            let suffix = self.base_digits()?;
+            self.in_base(Condition::Suffix(suffix))
+        } else if self.eat("contains:") {
+            let infix = self.base_digits()?;
+            self.in_base(Condition::Contains(infix))
+        } else if self.eat("excludes:") {
+            Condition::Excludes(self.digits()?)
+        } else if self.eat("luhn") {
+            Condition::Luhn
+        } else if self.eat("mirror") {
+            Condition::Mirror
+        } else if self.eat("count:") {
+            self.digit_count()?
+        } else if self.eat("groups:") {
+            Condition::Grouped(self.word()?)
+        } else if self.eat("monotonic:") {
+            Condition::Monotonic(self.word()?)
+        } else if self.eat("hashzeros:") {
+            let bits = self.digits()?;
+            hash_leading_zeros(bits.parse().map_err(|_| {
+                format!("Leading zero bits must be between 1 and 64, got {}", bits)
+            })?)?
+        } else {
+            let expected: Vec<String> = CONDITION_KEYWORDS
+                .iter()
+                .map(|keyword| format!("\"{}\"", keyword))
+                .collect();
+            return Err(format!(
+                "Expected one of {} or \"(\" at position {}",
+                expected.join(", "),
+                self.pos
+            ));
+        };
+        Ok(Pattern::Condition(condition))
+    }
+}
+
 pub struct PubkeyMatcher {
-    max_address_value: u64,
+    pattern: Pattern,
+    needs_digits: bool,
+    // The largest matching address if the pattern is a plain max length, which is then
+    // checked with a single comparison
+    max_value: Option<u64>,
+    // Digits of the address to get as close as possible to (see --target)
+    target: Option<String>,
 }
 
 impl PubkeyMatcher {
     pub fn new(max_len: usize) -> PubkeyMatcher {
         assert!(max_len >= 1);
 
+        PubkeyMatcher::from_pattern(Pattern::Condition(Condition::MaxLength(max_len)))
+    }
+
+    pub fn from_pattern(pattern: Pattern) -> PubkeyMatcher {
+        let max_value = match pattern {
+            Pattern::Condition(Condition::MaxLength(max_len)) => Some(max_address(max_len)),
+            _ => None,
+        };
         PubkeyMatcher {
-            max_address_value: max_address(max_len),
+            needs_digits: pattern.needs_digits(),
+            max_value,
+            pattern,
+            target: None,
+        }
+    }
+
+    // Matches every address. Used for testing the output path.
+    pub fn accept_all() -> PubkeyMatcher {
+        PubkeyMatcher::new(MAX_ADDRESS_LENGTH)
+    }
+
+    pub fn pattern(&self) -> &Pattern {
+        &self.pattern
+    }
+
+    // Human readable description of what is searched, e.g. "max length 14"
+    pub fn describe(&self) -> String {
+        match self.target {
+            Some(ref target) => format!("{}, closest to {}L", self.pattern.describe(), target),
+            None => self.pattern.describe(),
         }
     }
 
     pub fn matches(&self, pubkey: &[u8; 32]) -> bool {
-        let address = pubkey_to_address(pubkey);
-        // longest address: 18446744073709551615 (20 chars)
-        //
-        // Example max_len = 15
-        // Short address: 999999999999999 (15 chars)
-        // Strict upper bound = 10^15 = 1000000000000000
-        return address <= self.max_address_value;
+        self.matches_address(pubkey_to_address(pubkey))
+    }
+
+    pub fn matches_address(&self, address: u64) -> bool {
+        if let Some(max_value) = self.max_value {
+            return address <= max_value;
+        }
+        // Only render the address if any condition needs it
+        if self.needs_digits {
+            self.pattern.matches(address, &address.to_string())
+        } else {
+            self.pattern.matches(address, "")
+        }
+    }
+
+    // The largest address value the GPU kernel should report, if the GPU can evaluate this
+    // matcher. The kernel only supports length conditions.
+    pub fn gpu_max_address_value(&self) -> Option<u64> {
+        self.max_value
+    }
+
+    // A matcher for the same pattern made easier by one level (see --preview)
+    pub fn relaxed(&self) -> PubkeyMatcher {
+        PubkeyMatcher::from_pattern(self.pattern.relaxed())
+    }
+
+    // False if the pattern provably never matches an address
+    pub fn can_match(&self) -> bool {
+        self.pattern.can_match()
+    }
+
+    // Addresses are the first 8 bytes of a SHA-256 hash, i.e. uniformly distributed
+    // u64 values. So an address of at most N digits is simply a value < 10^N and the
+    // probability for a random key to match is 10^N / 2^64 (capped at 1).
+    pub fn match_probability(&self) -> f64 {
+        self.pattern.probability().min(1.0)
+    }
+
+    // Scores addresses by the digits they share with `target` instead of their length
+    pub fn with_target(mut self, target: u64) -> PubkeyMatcher {
+        self.target = Some(target.to_string());
+        self
     }
 
+    // Score for "best so far" searches. Lower is better, i.e. shorter addresses win or,
+    // with a target, addresses sharing more leading and trailing digits with it. Takes the
+    // address such that the search hashes every public key only once.
+    pub fn score_address(&self, address: u64) -> u64 {
+        match self.target {
+            Some(ref target) => {
+                (2 * MAX_ADDRESS_LENGTH - shared_digits(&address.to_string(), target)) as u64
+            }
+            None => address,
+        }
+    }
+
+    // 1 / match_probability(), computed exactly and truncated where possible
     pub fn estimated_attempts(&self) -> BigInt {
-        let number_of_good = BigInt::from(self.max_address_value) + BigInt::from(1);
-        return (BigInt::from(1) << 64) / number_of_good;
+        pattern_estimated_attempts(&self.pattern)
+    }
+
+    // Conditions that never matched a sampled address, e.g. a rare or impossible regular
+    // expression like "^x". If there are any, the estimated attempts are only a lower bound.
+    pub fn unsampled_conditions(&self) -> Vec<String> {
+        self.pattern.unsampled_conditions()
+    }
+
+    // Description and estimated attempts of every part of a combined pattern, to show which
+    // one dominates the estimate, and whether the estimate is only a lower bound (see
+    // `unsampled_conditions`). Empty for a single condition.
+    pub fn estimate_breakdown(&self) -> Vec<(String, BigInt, bool)> {
+        match self.pattern {
+            Pattern::All(ref patterns) | Pattern::Any(ref patterns) => patterns
+                .iter()
+                .map(|p| {
+                    (
+                        p.describe(),
+                        pattern_estimated_attempts(p),
+                        !p.unsampled_conditions().is_empty(),
+                    )
+                })
+                .collect(),
+            Pattern::Condition(_) => Vec::new(),
+        }
+    }
+}
+
+// Exact for patterns with a known match count
+fn pattern_estimated_attempts(pattern: &Pattern) -> BigInt {
+    if let Some(count) = pattern.match_count().filter(|&count| count > 0) {
+        return (BigInt::from(1) << 64) / big_int_from_u128(count);
+    }
+    BigInt::from_f64((1.0 / pattern.probability().min(1.0)).floor())
+        .unwrap_or_else(|| BigInt::from(1) << 64)
+}
+
+// Parses comma separated digit count rules like "7>=3,0<=1" (see --digit-count)
+pub fn parse_digit_count_rules(input: &str) -> Result<Vec<Condition>, String> {
+    let mut parser = PatternParser {
+        input,
+        pos: 0,
+        base: 10,
+    };
+    let mut conditions = vec![parser.digit_count()?];
+    while parser.eat(",") {
+        conditions.push(parser.digit_count()?);
+    }
+    parser.skip_whitespace();
+    if !parser.rest().is_empty() {
+        return Err(format!(
+            "Unexpected \"{}\" at position {}",
+            parser.rest(),
+            parser.pos
+        ));
+    }
+    Ok(conditions)
+}
+
+// Decimal digits of a command line option, e.g. "777"
+fn digits_argument(input: &str, base: u32) -> Result<String, String> {
+    let mut parser = PatternParser {
+        input,
+        pos: 0,
+        base,
+    };
+    let digits = parser.base_digits()?;
+    if !parser.rest().is_empty() {
+        return Err(format!(
+            "Unexpected \"{}\" at position {}",
+            parser.rest(),
+            parser.pos
+        ));
+    }
+    Ok(digits)
+}
+
+// The first digits of the address in `base` (see --prefix and --base)
+pub fn parse_prefix(input: &str, base: u32) -> Result<Condition, String> {
+    let prefix = digits_argument(input, base)?;
+    if prefix.len() > 1 && prefix.starts_with('0') {
+        return Err(format!(
+            "Prefix \"{}\" can never match since addresses have no leading zeros",
+            prefix
+        ));
+    }
+    Ok(in_base(base, Condition::Prefix(prefix)))
+}
+
+// Digits anywhere in the address in `base` (see --contains and --base)
+pub fn parse_contains(input: &str, base: u32) -> Result<Condition, String> {
+    Ok(in_base(
+        base,
+        Condition::Contains(digits_argument(input, base)?),
+    ))
+}
+
+// The last digits of the address in `base`, with or without the "L" suffix of the decimal
+// address (see --suffix and --base)
+pub fn parse_suffix(input: &str, base: u32) -> Result<Condition, String> {
+    let digits = match base {
+        10 => input.strip_suffix('L').unwrap_or(input),
+        _ => input,
+    };
+    Ok(in_base(
+        base,
+        Condition::Suffix(digits_argument(digits, base)?),
+    ))
+}
+
+// Addresses containing any of the comma separated `lucky` digits and none of the `unlucky`
+// ones anywhere (see --lucky and --unlucky), e.g. "8,88,888" and "4"
+pub fn lucky_patterns(lucky: Option<&str>, unlucky: Option<&str>) -> Result<Vec<Pattern>, String> {
+    let digit_list = |input: &str| -> Result<Vec<String>, String> {
+        let mut parser = PatternParser {
+            input,
+            pos: 0,
+            base: 10,
+        };
+        let mut list = vec![parser.digits()?];
+        while parser.eat(",") {
+            list.push(parser.digits()?);
+        }
+        parser.skip_whitespace();
+        if !parser.rest().is_empty() {
+            return Err(format!(
+                "Unexpected \"{}\" at position {}",
+                parser.rest(),
+                parser.pos
+            ));
+        }
+        Ok(list)
+    };
+    let mut patterns = Vec::new();
+    let unlucky = match unlucky {
+        Some(unlucky) => digit_list(unlucky)?,
+        None => Vec::new(),
+    };
+    if let Some(lucky) = lucky {
+        let lucky: Vec<String> = digit_list(lucky)?
+            .into_iter()
+            .filter(|digits| {
+                !unlucky
+                    .iter()
+                    .any(|excluded| digits.contains(excluded.as_str()))
+            })
+            .collect();
+        if lucky.is_empty() {
+            return Err("Every lucky number contains an unlucky one".to_string());
+        }
+        patterns.push(Pattern::Any(
+            lucky
+                .into_iter()
+                .map(|digits| Pattern::Condition(Condition::Contains(digits)))
+                .collect(),
+        ));
+    }
+    patterns.extend(
+        unlucky
+            .into_iter()
+            .map(|digits| Pattern::Condition(Condition::Excludes(digits))),
+    );
+    Ok(patterns)
+}
+
+// Parses an address given with or without the "L" suffix
+pub fn parse_address(address: &str) -> Result<u64, String> {
+    let digits = address.trim().trim_end_matches('L');
+    if digits.is_empty()
+        || !digits.bytes().all(|byte| byte.is_ascii_digit())
+        || (digits.len() > 1 && digits.starts_with('0'))
+    {
+        return Err(format!("Invalid address \"{}\"", address));
+    }
+    digits
+        .parse()
+        .map_err(|_| format!("Invalid address \"{}\"", address))
+}
+
+// The number of leading plus trailing digits two addresses have in common
+fn shared_digits(a: &str, b: &str) -> usize {
+    let prefix = a.bytes().zip(b.bytes()).take_while(|(x, y)| x == y).count();
+    let suffix = a
+        .bytes()
+        .rev()
+        .zip(b.bytes().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    prefix + suffix
+}
+
+// Conditions for addresses sharing the first `prefix_len` and last `suffix_len` digits with
+// the given address (see --like). The address may be given with or without the "L" suffix.
+pub fn like_conditions(
+    address: &str,
+    prefix_len: usize,
+    suffix_len: usize,
+) -> Result<Vec<Condition>, String> {
+    let digits = parse_address(address)?.to_string();
+    if prefix_len > digits.len() || suffix_len > digits.len() {
+        return Err(format!(
+            "Address \"{}\" has less than {} digits",
+            address,
+            prefix_len.max(suffix_len)
+        ));
+    }
+
+    let mut conditions = Vec::new();
+    if prefix_len > 0 {
+        conditions.push(Condition::Prefix(digits[..prefix_len].to_string()));
+    }
+    if suffix_len > 0 {
+        conditions.push(Condition::Suffix(
+            digits[digits.len() - suffix_len..].to_string(),
+        ));
     }
+    Ok(conditions)
+}
+
+impl FromStr for PubkeyMatcher {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<PubkeyMatcher, String> {
+        PubkeyMatcher::parse_in_base(input, 10)
+    }
+}
+
+impl PubkeyMatcher {
+    // Parses a pattern whose prefix, suffix and contains conditions apply to the address in
+    // `base` (2 to 36). All other conditions keep using the decimal address.
+    pub fn parse_in_base(input: &str, base: u32) -> Result<PubkeyMatcher, String> {
+        assert!((2..=36).contains(&base));
+        if input.trim().is_empty() {
+            return Err("Pattern is empty".to_string());
+        }
+        let mut parser = PatternParser {
+            input,
+            pos: 0,
+            base,
+        };
+        let pattern = parser.pattern()?;
+        parser.skip_whitespace();
+        if !parser.rest().is_empty() {
+            return Err(format!(
+                "Unexpected \"{}\" at position {}",
+                parser.rest(),
+                parser.pos
+            ));
+        }
+        Ok(PubkeyMatcher::from_pattern(pattern))
+    }
+}
+
+// One pattern of a patterns file, remembered to tell which goals a match satisfies
+pub struct PatternGoal {
+    // 1-based line number in the file
+    pub line: usize,
+    pub matcher: PubkeyMatcher,
+}
+
+impl PatternGoal {
+    // e.g. "line 3 (suffix 777)"
+    pub fn describe(&self) -> String {
+        format!("line {} ({})", self.line, self.matcher.describe())
+    }
+}
+
+// Parses a patterns file (see --patterns-file), one pattern per line. Empty lines and lines
+// starting with "#" are ignored.
+pub fn parse_patterns_file(content: &str, base: u32) -> Result<Vec<PatternGoal>, String> {
+    let mut goals = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let matcher = PubkeyMatcher::parse_in_base(line, base)
+            .map_err(|error| format!("Line {}: {}", index + 1, error))?;
+        goals.push(PatternGoal {
+            line: index + 1,
+            matcher,
+        });
+    }
+    if goals.is_empty() {
+        return Err("No patterns found".to_string());
+    }
+    Ok(goals)
 }
 
 #[cfg(test)]
 mod tests {
     // importing names from outer (for mod tests) scope.
     use super::*;
+    use derivation::{secret_to_pubkey, GenerateKeyType};
+
+    #[test]
+    fn test_max_length_fast_path() {
+        for matcher in &[
+            PubkeyMatcher::new(12),
+            "len<=12".parse::<PubkeyMatcher>().unwrap(),
+        ] {
+            assert_eq!(matcher.max_value, Some(999_999_999_999));
+            assert!(!matcher.needs_digits);
+            assert!(matcher.matches_address(999_999_999_999));
+            assert!(!matcher.matches_address(1_000_000_000_000));
+        }
+        // combinations take the general path
+        let combined: PubkeyMatcher = "len<=12 & luhn".parse().unwrap();
+        assert_eq!(combined.max_value, None);
+        assert_eq!(combined.gpu_max_address_value(), None);
+    }
 
     #[test]
     fn test_max_address() {
@@ -57,6 +1532,23 @@ mod tests {
         assert_eq!(max_address(1), 9u64);
     }
 
+    #[test]
+    fn test_address_length() {
+        assert_eq!(address_length(0), 1);
+        assert_eq!(address_length(9), 1);
+        assert_eq!(address_length(10), 2);
+        assert_eq!(address_length(999999999999999), 15);
+        assert_eq!(address_length(1000000000000000), 16);
+        assert_eq!(address_length(18446744073709551615), 20);
+    }
+
+    #[test]
+    fn test_address_space_coverage() {
+        assert_eq!(address_space_coverage(0), 0.0);
+        assert_eq!(address_space_coverage(1 << 32), 1.0 / (1u64 << 32) as f64);
+        assert_eq!(address_space_coverage(1 << 63), 0.5);
+    }
+
     #[test]
     fn test_estimated_attempts() {
         let matcher_all = PubkeyMatcher::new(10000);
@@ -83,4 +1575,941 @@ mod tests {
         let estimated = matcher_three.estimated_attempts();
         assert_eq!(estimated, BigInt::from(18446744073709551u64));
     }
+
+    #[test]
+    fn test_match_probability() {
+        assert_eq!(PubkeyMatcher::new(10000).match_probability(), 1.0);
+        assert_eq!(PubkeyMatcher::new(20).match_probability(), 1.0);
+        // 10^19 / 2^64
+        assert!((PubkeyMatcher::new(19).match_probability() - 0.5421010862).abs() < 1e-9);
+        // 10^10 / 2^64
+        assert!((PubkeyMatcher::new(10).match_probability() - 5.421010862e-10).abs() < 1e-18);
+    }
+
+    #[test]
+    fn test_estimated_attempts_empirical() {
+        // With max length 19 about 54% of all addresses match, so a few thousand
+        // derivations are enough to compare the model against reality.
+        let matcher = PubkeyMatcher::new(19);
+        let samples = 2000;
+        let mut key = [0u8; 32];
+        key[0] = 0x42;
+        let mut matches = 0;
+        for i in 0..samples {
+            key[30] = (i >> 8) as u8;
+            key[31] = i as u8;
+            let pubkey = secret_to_pubkey(key, GenerateKeyType::PrivateKey);
+            if matcher.matches(&pubkey) {
+                matches += 1;
+            }
+        }
+        let observed = matches as f64 / samples as f64;
+        assert!((observed - matcher.match_probability()).abs() < 0.05);
+        assert_eq!(matcher.estimated_attempts(), BigInt::from(1));
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(
+            "len<=12".parse::<PubkeyMatcher>().unwrap().pattern,
+            Pattern::Condition(Condition::MaxLength(12))
+        );
+        assert_eq!(
+            " suffix:777 & contains:8888 "
+                .parse::<PubkeyMatcher>()
+                .unwrap()
+                .pattern,
+            Pattern::All(vec![
+                Pattern::Condition(Condition::Suffix("777".to_string())),
+                Pattern::Condition(Condition::Contains("8888".to_string())),
+            ])
+        );
+        // "&" binds stronger than "|"
+        assert_eq!(
+            "prefix:1|suffix:2&len<=5"
+                .parse::<PubkeyMatcher>()
+                .unwrap()
+                .pattern,
+            Pattern::Any(vec![
+                Pattern::Condition(Condition::Prefix("1".to_string())),
+                Pattern::All(vec![
+                    Pattern::Condition(Condition::Suffix("2".to_string())),
+                    Pattern::Condition(Condition::MaxLength(5)),
+                ]),
+            ])
+        );
+        assert_eq!(
+            "(prefix:1 | suffix:2) & len<=5"
+                .parse::<PubkeyMatcher>()
+                .unwrap()
+                .pattern,
+            Pattern::All(vec![
+                Pattern::Any(vec![
+                    Pattern::Condition(Condition::Prefix("1".to_string())),
+                    Pattern::Condition(Condition::Suffix("2".to_string())),
+                ]),
+                Pattern::Condition(Condition::MaxLength(5)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_from_str_malformed() {
+        assert_eq!(
+            "".parse::<PubkeyMatcher>().err(),
+            Some("Pattern is empty".to_string())
+        );
+        assert_eq!(
+            "   ".parse::<PubkeyMatcher>().err(),
+            Some("Pattern is empty".to_string())
+        );
+        assert!("len<=".parse::<PubkeyMatcher>().is_err());
+        assert!("len<=0".parse::<PubkeyMatcher>().is_err());
+        assert_eq!(
+            "len<=99999999999999999999".parse::<PubkeyMatcher>().err(),
+            Some("Number 99999999999999999999 at position 5 is too large".to_string())
+        );
+        assert!("len<12".parse::<PubkeyMatcher>().is_err());
+        assert!("suffix:".parse::<PubkeyMatcher>().is_err());
+        assert!("suffix:abc".parse::<PubkeyMatcher>().is_err());
+        assert!("suffix:77a".parse::<PubkeyMatcher>().is_err());
+        assert!("prefix:012".parse::<PubkeyMatcher>().is_err());
+        assert!("contains:123456789012345678901"
+            .parse::<PubkeyMatcher>()
+            .is_err());
+        assert!("middle:5".parse::<PubkeyMatcher>().is_err());
+        assert!("suffix:7 &".parse::<PubkeyMatcher>().is_err());
+        assert!("| suffix:7".parse::<PubkeyMatcher>().is_err());
+        assert!("suffix:7 suffix:8".parse::<PubkeyMatcher>().is_err());
+        assert!("(suffix:7".parse::<PubkeyMatcher>().is_err());
+        assert!("suffix:7)".parse::<PubkeyMatcher>().is_err());
+    }
+
+    #[test]
+    fn test_matches_address() {
+        let matcher: PubkeyMatcher = "suffix:777 & contains:8888".parse().unwrap();
+        assert!(matcher.matches_address(18888777));
+        assert!(matcher.matches_address(88881777));
+        assert!(!matcher.matches_address(8888));
+        assert!(!matcher.matches_address(888777));
+
+        let matcher: PubkeyMatcher = "prefix:100 | len<=3".parse().unwrap();
+        assert!(matcher.matches_address(100));
+        assert!(matcher.matches_address(1001234));
+        assert!(matcher.matches_address(999));
+        assert!(!matcher.matches_address(1010));
+
+        let matcher: PubkeyMatcher = "suffix:07".parse().unwrap();
+        assert!(matcher.matches_address(107));
+        assert!(!matcher.matches_address(7));
+    }
+
+    #[test]
+    fn test_match_count() {
+        // 1, 10-19, 100-199, ... 10^19-(2*10^19-1) capped at 2^64
+        let count = Condition::Prefix("1".to_string()).match_count().unwrap();
+        assert_eq!(
+            count,
+            1111111111111111111 + (ADDRESS_SPACE - 10000000000000000000)
+        );
+        assert_eq!(Condition::Prefix("0".to_string()).match_count(), Some(1));
+        assert_eq!(
+            Condition::Prefix("2".to_string()).match_count(),
+            Some(1111111111111111111)
+        );
+        assert_eq!(
+            Condition::Prefix("18446744073709551615".to_string()).match_count(),
+            Some(1)
+        );
+        assert_eq!(
+            Condition::Prefix("18446744073709551616".to_string()).match_count(),
+            Some(0)
+        );
+
+        // 7, 17, 27, ..., 18446744073709551607
+        assert_eq!(
+            Condition::Suffix("7".to_string()).match_count(),
+            Some(1844674407370955161)
+        );
+        // 107, 207, ..., but not 7
+        assert_eq!(
+            Condition::Suffix("07".to_string()).match_count(),
+            Some(184467440737095517 - 1)
+        );
+    }
+
+    #[test]
+    fn test_pattern_estimated_attempts() {
+        let matcher: PubkeyMatcher = "suffix:777".parse().unwrap();
+        assert_eq!(matcher.estimated_attempts(), BigInt::from(1000));
+
+        let matcher: PubkeyMatcher = "suffix:77 & len<=19".parse().unwrap();
+        assert!((matcher.match_probability() - 0.01 * 0.5421010862).abs() < 1e-9);
+
+        let matcher: PubkeyMatcher = "suffix:1 | suffix:2".parse().unwrap();
+        assert!((matcher.match_probability() - 0.19).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_length_correlated_estimates() {
+        // 123 is the only match
+        let matcher: PubkeyMatcher = "len==3 & prefix:123".parse().unwrap();
+        assert_eq!(matcher.estimated_attempts(), BigInt::from(1) << 64);
+        // 1 + 10 + ... + 10^6 matches with 4 to 10 digits
+        let matcher: PubkeyMatcher = "len<=10 & prefix:1234".parse().unwrap();
+        assert_eq!(
+            matcher.estimated_attempts(),
+            (BigInt::from(1) << 64) / BigInt::from(1111111)
+        );
+        let matcher: PubkeyMatcher = "suffix:42 & len==3".parse().unwrap();
+        assert_eq!(
+            matcher.estimated_attempts(),
+            (BigInt::from(1) << 64) / BigInt::from(9)
+        );
+        // Mirrored addresses have an odd length
+        let matcher: PubkeyMatcher = "len==12 & mirror".parse().unwrap();
+        assert_eq!(matcher.match_probability(), 0.0);
+        // 1 to 9, 101 to 191 and 10001 to 19991: 111 matches, but not calculated exactly
+        let matcher: PubkeyMatcher = "mirror & prefix:1 & len<=5".parse().unwrap();
+        let expected = 111.0 / ADDRESS_SPACE as f64;
+        assert!((matcher.match_probability() - expected).abs() < expected * 1e-9);
+    }
+
+    #[test]
+    fn test_length_match_counts() {
+        for condition in &[
+            Condition::Prefix("0".to_string()),
+            Condition::Prefix("1844".to_string()),
+            Condition::Prefix("19".to_string()),
+            Condition::Suffix("0".to_string()),
+            Condition::Suffix("07".to_string()),
+            Condition::Suffix("615".to_string()),
+            Condition::Mirror,
+            Condition::MaxLength(7),
+            Condition::LengthRange(3, 20),
+        ] {
+            let counts = condition.length_match_counts().unwrap();
+            let total: u128 = counts.iter().sum();
+            match condition {
+                // `suffix_count` does not count 0 itself as ending with "0"
+                Condition::Suffix(suffix) if suffix == "0" => {
+                    assert_eq!(total, condition.match_count().unwrap() + 1)
+                }
+                _ => assert_eq!(total, condition.match_count().unwrap(), "{:?}", condition),
+            }
+            for (len, count) in (1..).zip(counts) {
+                assert!(count <= length_range_count(len, len));
+            }
+        }
+        let counts = Condition::Prefix("12".to_string())
+            .length_match_counts()
+            .unwrap();
+        assert_eq!(&counts[..4], &[0, 1, 10, 100]);
+    }
+
+    #[test]
+    fn test_exact_length() {
+        let matcher: PubkeyMatcher = "len==10".parse().unwrap();
+        assert!(matcher.matches_address(1000000000));
+        assert!(matcher.matches_address(9999999999));
+        assert!(!matcher.matches_address(999999999));
+        assert!(!matcher.matches_address(10000000000));
+
+        assert_eq!(Condition::ExactLength(1).match_count(), Some(10));
+        assert_eq!(Condition::ExactLength(10).match_count(), Some(9000000000));
+        // 10^19 up to 2^64 - 1
+        assert_eq!(
+            Condition::ExactLength(20).match_count(),
+            Some(ADDRESS_SPACE - 10000000000000000000)
+        );
+        // truncate(2^64 / (9 * 10^9))
+        assert_eq!(matcher.estimated_attempts(), BigInt::from(2049638230u64));
+
+        assert!("len==0".parse::<PubkeyMatcher>().is_err());
+        assert!("len==21".parse::<PubkeyMatcher>().is_err());
+        assert!("len==99999999999999999999"
+            .parse::<PubkeyMatcher>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_length_band() {
+        let matcher = PubkeyMatcher::from_pattern(Pattern::Condition(length_band(12, 2)));
+        assert!(matcher.matches_address(1000000000));
+        assert!(matcher.matches_address(99999999999999));
+        assert!(!matcher.matches_address(999999999));
+        assert!(!matcher.matches_address(100000000000000));
+        // 10^14 - 10^9 addresses
+        assert_eq!(matcher.estimated_attempts(), BigInt::from(184469));
+        assert_eq!(matcher.describe(), "length 10 to 14");
+
+        // clipped to the address lengths
+        assert_eq!(length_band(2, 3), Condition::LengthRange(1, 5));
+        assert_eq!(length_band(19, 3), Condition::LengthRange(16, 20));
+        let matcher = PubkeyMatcher::from_pattern(Pattern::Condition(length_band(1, 1)));
+        assert!(matcher.matches_address(0));
+        assert!(matcher.matches_address(99));
+        let matcher = PubkeyMatcher::from_pattern(Pattern::Condition(length_band(20, 0)));
+        assert!(matcher.matches_address(u64::MAX));
+        assert_eq!(
+            Condition::LengthRange(20, 20).match_count(),
+            Condition::ExactLength(20).match_count()
+        );
+        assert_eq!(
+            Condition::LengthRange(1, 20).match_count(),
+            Some(ADDRESS_SPACE)
+        );
+        assert_eq!(Condition::LengthRange(5, 4).match_count(), Some(0));
+
+        // --min-length 8 with LENGTH 10
+        let matcher =
+            PubkeyMatcher::from_pattern(Pattern::Condition(Condition::LengthRange(8, 10)));
+        assert!(!matcher.matches_address(9999999));
+        assert!(matcher.matches_address(10000000));
+        assert!(matcher.matches_address(9999999999));
+        assert!(!matcher.matches_address(10000000000));
+        // 10^10 - 10^7 addresses
+        assert_eq!(matcher.estimated_attempts(), BigInt::from(1846520928u64));
+    }
+
+    #[test]
+    fn test_digit_count() {
+        assert_eq!(
+            parse_digit_count_rules("7>=3, 0<=1").unwrap(),
+            vec![
+                Condition::DigitCount(7, Comparison::AtLeast, 3),
+                Condition::DigitCount(0, Comparison::AtMost, 1),
+            ]
+        );
+        assert_eq!(
+            parse_digit_count_rules("5=0").unwrap(),
+            vec![Condition::DigitCount(5, Comparison::Exactly, 0)]
+        );
+        assert!(parse_digit_count_rules("").is_err());
+        assert!(parse_digit_count_rules("7").is_err());
+        assert!(parse_digit_count_rules("7>3").is_err());
+        assert!(parse_digit_count_rules("x>=3").is_err());
+        assert!(parse_digit_count_rules("7>=").is_err());
+        assert!(parse_digit_count_rules("7>=3,").is_err());
+        assert!(parse_digit_count_rules("7>=3;0<=1").is_err());
+        assert!("count:7>=99999999999999999999"
+            .parse::<PubkeyMatcher>()
+            .is_err());
+
+        let matcher: PubkeyMatcher = "count:7>=3 & count:0<=1".parse().unwrap();
+        assert!(matcher.matches_address(777));
+        assert!(matcher.matches_address(1077787));
+        assert!(!matcher.matches_address(77));
+        assert!(!matcher.matches_address(70077));
+
+        // every address has at least 0 sevens
+        let matcher: PubkeyMatcher = "count:7>=0".parse().unwrap();
+        assert!((matcher.match_probability() - 1.0).abs() < 1e-9);
+        // 0.9^19
+        let matcher: PubkeyMatcher = "count:7=0".parse().unwrap();
+        assert!((matcher.match_probability() - 0.9f64.powi(19)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_relaxed() {
+        let matcher: PubkeyMatcher = "len<=12 & prefix:123 | suffix:4567 & count:7>=3 & count:0<=1"
+            .parse()
+            .unwrap();
+        let expected: PubkeyMatcher = "len<=13 & prefix:12 | suffix:567 & count:7>=2 & count:0<=2"
+            .parse()
+            .unwrap();
+        assert_eq!(matcher.relaxed().pattern(), expected.pattern());
+
+        // unchanged
+        let matcher: PubkeyMatcher = "len==12 & luhn & count:1=3 & len<=20".parse().unwrap();
+        assert_eq!(matcher.relaxed().pattern(), matcher.pattern());
+
+        let matcher: PubkeyMatcher = "suffix:7".parse().unwrap();
+        assert!(matcher.relaxed().matches_address(1234));
+        assert_eq!(matcher.relaxed().estimated_attempts(), BigInt::from(1));
+        assert!(matcher.relaxed().match_probability() > matcher.match_probability());
+    }
+
+    #[test]
+    fn test_estimate_breakdown() {
+        let all: PubkeyMatcher = "suffix:777 & len<=18".parse().unwrap();
+        assert_eq!(
+            all.estimate_breakdown(),
+            vec![
+                ("suffix 777".to_string(), BigInt::from(1000), false),
+                ("max length 18".to_string(), BigInt::from(18), false),
+            ]
+        );
+        // 1000 * 2^64 / 10^18
+        assert_eq!(all.estimated_attempts(), BigInt::from(18446));
+
+        let any: PubkeyMatcher = "suffix:777 | (suffix:778 & luhn)".parse().unwrap();
+        assert_eq!(
+            any.estimate_breakdown(),
+            vec![
+                ("suffix 777".to_string(), BigInt::from(1000), false),
+                (
+                    "suffix 778 and Luhn checksum".to_string(),
+                    BigInt::from(10000),
+                    false
+                ),
+            ]
+        );
+        // 1 / (1 - (1 - 1/1000) * (1 - 1/10000))
+        assert_eq!(any.estimated_attempts(), BigInt::from(909));
+
+        assert!("len<=12"
+            .parse::<PubkeyMatcher>()
+            .unwrap()
+            .estimate_breakdown()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_describe() {
+        let descriptions = [
+            ("len<=14", "max length 14"),
+            ("len==12", "length 12"),
+            ("prefix:123", "prefix 123"),
+            ("suffix:777", "suffix 777"),
+            ("contains:08", "contains 08"),
+            ("luhn", "Luhn checksum"),
+            ("count:7>=3", "at least 3 times digit 7"),
+            ("count:0<=1", "at most 1 times digit 0"),
+            ("count:5=2", "exactly 2 times digit 5"),
+            ("groups:equal", "equal digit groups"),
+            ("groups:palindrome", "palindromic digit groups"),
+            ("monotonic:inc", "increasing digits"),
+            ("monotonic:dec", "decreasing digits"),
+            ("suffix:7 & luhn", "suffix 7 and Luhn checksum"),
+            (
+                "len<=12 | prefix:1 & (suffix:2 | luhn)",
+                "max length 12 or (prefix 1 and (suffix 2 or Luhn checksum))",
+            ),
+        ];
+        for &(pattern, description) in descriptions.iter() {
+            let matcher: PubkeyMatcher = pattern.parse().unwrap();
+            assert_eq!(matcher.describe(), description, "{}", pattern);
+        }
+
+        let matcher = PubkeyMatcher::new(20).with_target(123);
+        assert_eq!(matcher.describe(), "max length 20, closest to 123L");
+    }
+
+    #[test]
+    fn test_can_match() {
+        let can_match = |pattern: &str| pattern.parse::<PubkeyMatcher>().unwrap().can_match();
+        assert!(can_match("len<=3"));
+        assert!(can_match("len<=4 & prefix:1234"));
+        assert!(can_match("len<=3 & prefix:1234 | suffix:7"));
+        assert!(can_match("count:1>=20"));
+        assert!(can_match("count:1<=25"));
+        assert!(!can_match("len<=3 & prefix:1234"));
+        assert!(!can_match("len==5 & contains:123456"));
+        assert!(!can_match("len==5 & len<=4"));
+        assert!(!can_match("len==5 & count:7=6"));
+        assert!(!can_match("prefix:99999999999999999999"));
+        assert!(!can_match("suffix:99999999999999999999"));
+        assert!(!can_match("count:1>=21"));
+        assert!(!can_match(
+            "(len<=3 & suffix:1234) | prefix:99999999999999999999"
+        ));
+    }
+
+    #[test]
+    fn test_grouped() {
+        let equal: PubkeyMatcher = "groups:equal".parse().unwrap();
+        assert_eq!(
+            equal.pattern(),
+            &Pattern::Condition(Condition::Grouped(Grouping::EqualGroups))
+        );
+        assert!(equal.matches_address(123123));
+        assert!(equal.matches_address(777777777777777777));
+        assert!(!equal.matches_address(123));
+        assert!(!equal.matches_address(23123));
+        assert!(!equal.matches_address(123123124));
+
+        let palindrome: PubkeyMatcher = "groups:palindrome & len==9".parse().unwrap();
+        assert!(palindrome.matches_address(123456123));
+        assert!(!palindrome.matches_address(123456321));
+        assert!(!palindrome.matches_address(1234561));
+
+        // 900 per length 6, 9, ..., 18
+        assert_eq!(Grouping::EqualGroups.match_count(), 4500);
+        // 2 and 3 groups: 900 choices for the first group, ...
+        assert_eq!(
+            Grouping::Palindrome.match_count(),
+            900 + 900_000 + 900_000 + 900_000_000 + 900_000_000
+        );
+        assert!(!"groups:palindrome & len<=5"
+            .parse::<PubkeyMatcher>()
+            .unwrap()
+            .can_match());
+
+        assert!("groups:".parse::<PubkeyMatcher>().is_err());
+        assert!("groups:odd".parse::<PubkeyMatcher>().is_err());
+        assert_eq!("palindrome".parse(), Ok(Grouping::Palindrome));
+    }
+
+    #[test]
+    fn test_monotonic() {
+        let increasing: PubkeyMatcher = "monotonic:inc".parse().unwrap();
+        assert!(increasing.matches_address(1357));
+        assert!(increasing.matches_address(123456789));
+        assert!(increasing.matches_address(7));
+        assert!(!increasing.matches_address(975));
+        assert!(!increasing.matches_address(1337));
+        assert!(!increasing.matches_address(1354));
+
+        let decreasing: PubkeyMatcher = "monotonic:dec".parse().unwrap();
+        assert!(decreasing.matches_address(975));
+        assert!(decreasing.matches_address(9876543210));
+        assert!(!decreasing.matches_address(1357));
+        assert!(!decreasing.matches_address(9775));
+
+        // brute force over all addresses up to 6 digits
+        let count = |direction: Monotonic| {
+            (0..1_000_000u64)
+                .filter(|address| direction.matches(&address.to_string()))
+                .count() as u128
+        };
+        // plus the subsets of 7, 8 or 9 digits
+        let longer = |n: u32| -> u128 { (7..=n).map(|k| binomial_coefficient(n, k)).sum() };
+        assert_eq!(
+            count(Monotonic::Increasing) + longer(9),
+            Monotonic::Increasing.match_count()
+        );
+        assert_eq!(
+            count(Monotonic::Decreasing) + longer(10),
+            Monotonic::Decreasing.match_count()
+        );
+
+        assert!(!"monotonic:inc & len==10"
+            .parse::<PubkeyMatcher>()
+            .unwrap()
+            .can_match());
+        assert!("monotonic:up".parse::<PubkeyMatcher>().is_err());
+    }
+
+    #[test]
+    fn test_mirror() {
+        let mirror: PubkeyMatcher = "mirror".parse().unwrap();
+        assert!(mirror.matches_address(12321));
+        assert!(mirror.matches_address(12021));
+        assert!(mirror.matches_address(7));
+        assert!(!mirror.matches_address(12345));
+        assert!(!mirror.matches_address(1221));
+        assert!(!mirror.matches_address(12320));
+        assert_eq!(mirror.describe(), "mirrored digits");
+
+        // brute force over all addresses up to 6 digits: 10 + 90 + 900
+        let count = (0..1_000_000u64)
+            .filter(|address| mirrored(&address.to_string()))
+            .count();
+        assert_eq!(count, 1000);
+        assert_eq!(mirrored_match_count(), 10_000_000_000);
+        // truncate(2^64 / 10^10)
+        assert_eq!(mirror.estimated_attempts(), BigInt::from(1844674407u64));
+
+        assert!(!"mirror & len==20"
+            .parse::<PubkeyMatcher>()
+            .unwrap()
+            .can_match());
+        // Mirrored addresses have an odd length
+        let can_match = |pattern: &str| pattern.parse::<PubkeyMatcher>().unwrap().can_match();
+        assert!(!can_match("mirror & len==12"));
+        assert!(!can_match("len==12 & prefix:1 & mirror"));
+        assert!(can_match("mirror & len==13"));
+        assert!(can_match("mirror & len<=12"));
+    }
+
+    #[test]
+    fn test_parse_prefix() {
+        assert_eq!(
+            parse_prefix("1234", 10),
+            Ok(Condition::Prefix("1234".to_string()))
+        );
+        // only the address 0 starts with 0
+        assert_eq!(
+            parse_prefix("0", 10),
+            Ok(Condition::Prefix("0".to_string()))
+        );
+        assert!(parse_prefix("0123", 10).is_err());
+        assert!(parse_prefix("12L", 10).is_err());
+        assert!(parse_prefix("", 10).is_err());
+
+        let matcher =
+            PubkeyMatcher::from_pattern(Pattern::Condition(parse_prefix("1234", 10).unwrap()));
+        assert!(matcher.matches_address(1234));
+        assert!(matcher.matches_address(12345678901234567890));
+        assert!(!matcher.matches_address(1235));
+        assert!(!matcher.matches_address(123));
+        // 1234 followed by 0 to 16 digits, all below 2^64
+        assert_eq!(matcher.estimated_attempts(), BigInt::from(1660));
+
+        let zero = PubkeyMatcher::from_pattern(Pattern::Condition(parse_prefix("0", 10).unwrap()));
+        assert!(zero.can_match());
+        assert!(zero.matches_address(0));
+        assert!(!zero.matches_address(10));
+
+        // In another base the prefix applies to the address in that base
+        assert_eq!(
+            parse_prefix("Cafe", 16),
+            Ok(Condition::InBase(
+                16,
+                Box::new(Condition::Prefix("cafe".to_string()))
+            ))
+        );
+        assert!(parse_prefix("0f", 16).is_err());
+        assert!(parse_prefix("2", 2).is_err());
+        let matcher =
+            PubkeyMatcher::from_pattern(Pattern::Condition(parse_prefix("cafe", 16).unwrap()));
+        assert!(matcher.matches_address(0xcafe_1234));
+        assert!(!matcher.matches_address(0xcaf));
+    }
+
+    #[test]
+    fn test_regex_condition() {
+        let matcher = PubkeyMatcher::from_pattern(Pattern::All(vec![
+            Pattern::Condition(regex_condition("^1[0-4]+9L$").unwrap()),
+            Pattern::Condition(Condition::MaxLength(6)),
+        ]));
+        assert!(matcher.matches_address(10429));
+        assert!(!matcher.matches_address(10459));
+        assert!(!matcher.matches_address(1042399));
+        assert_eq!(matcher.describe(), "regex ^1[0-4]+9L$ and max length 6");
+        assert!(regex_condition("(7").is_err());
+
+        // about half of the addresses have 20 digits
+        let twenty_digits: PubkeyMatcher = "len==20".parse().unwrap();
+        let regex =
+            PubkeyMatcher::from_pattern(Pattern::Condition(regex_condition("^\\d{20}L$").unwrap()));
+        assert!(regex.matches_address(u64::MAX));
+        let ratio = regex.match_probability() / twenty_digits.match_probability();
+        assert!((0.98..1.02).contains(&ratio), "{}", ratio);
+        assert!(regex.unsampled_conditions().is_empty());
+        // never matched in the sample, so the estimate is only a bound
+        let rare = PubkeyMatcher::from_pattern(Pattern::Condition(regex_condition("^7L").unwrap()));
+        assert_eq!(rare.match_probability(), 1.0 / PROBABILITY_SAMPLES as f64);
+        assert_eq!(rare.unsampled_conditions(), vec!["regex ^7L".to_string()]);
+        let combined = PubkeyMatcher::from_pattern(Pattern::Any(vec![
+            Pattern::Condition(Condition::Suffix("7".to_string())),
+            Pattern::Condition(regex_condition("^x").unwrap()),
+        ]));
+        assert_eq!(
+            combined.unsampled_conditions(),
+            vec!["regex ^x".to_string()]
+        );
+        let breakdown = combined.estimate_breakdown();
+        assert!(!breakdown[0].2);
+        assert!(breakdown[1].2);
+    }
+
+    #[test]
+    fn test_parse_contains() {
+        assert_eq!(
+            parse_contains("0420", 10),
+            Ok(Condition::Contains("0420".to_string()))
+        );
+        assert!(parse_contains("42L", 10).is_err());
+
+        let matcher =
+            PubkeyMatcher::from_pattern(Pattern::Condition(parse_contains("420", 10).unwrap()));
+        assert!(matcher.matches_address(420));
+        assert!(matcher.matches_address(1234205));
+        assert!(!matcher.matches_address(4_024_024));
+        // ~17.4 positions with 1/1000 each, instead of 1/1000 for a suffix
+        assert_eq!(matcher.estimated_attempts(), BigInt::from(57));
+
+        // In another base the digits are searched in the address in that base
+        assert_eq!(
+            parse_contains("bEEf", 16),
+            Ok(Condition::InBase(
+                16,
+                Box::new(Condition::Contains("beef".to_string()))
+            ))
+        );
+        assert!(parse_contains("beeg", 16).is_err());
+        let matcher =
+            PubkeyMatcher::from_pattern(Pattern::Condition(parse_contains("beef", 16).unwrap()));
+        assert!(matcher.matches_address(0x12be_ef34));
+        assert!(!matcher.matches_address(0xbeee));
+    }
+
+    #[test]
+    fn test_parse_suffix() {
+        assert_eq!(
+            parse_suffix("777L", 10),
+            Ok(Condition::Suffix("777".to_string()))
+        );
+        assert_eq!(
+            parse_suffix("007", 10),
+            Ok(Condition::Suffix("007".to_string()))
+        );
+        assert!(parse_suffix("L", 10).is_err());
+        assert!(parse_suffix("777LL", 10).is_err());
+        assert!(parse_suffix("777LLL", 10).is_err());
+        assert!(parse_suffix("7a7", 10).is_err());
+        assert!(parse_suffix(&"1".repeat(21), 10).is_err());
+
+        let matcher =
+            PubkeyMatcher::from_pattern(Pattern::Condition(parse_suffix("777L", 10).unwrap()));
+        assert!(matcher.matches_address(1234777));
+        assert!(!matcher.matches_address(1234778));
+        assert_eq!(matcher.estimated_attempts(), BigInt::from(1000));
+
+        // In another base the suffix applies to the address in that base
+        assert_eq!(
+            parse_suffix("fF", 16),
+            Ok(Condition::InBase(
+                16,
+                Box::new(Condition::Suffix("ff".to_string()))
+            ))
+        );
+        assert!(parse_suffix("ffL", 16).is_err());
+        assert!(parse_suffix("12", 2).is_err());
+        let matcher =
+            PubkeyMatcher::from_pattern(Pattern::Condition(parse_suffix("ff", 16).unwrap()));
+        assert!(matcher.matches_address(0x12ff));
+        assert!(!matcher.matches_address(255012));
+        assert_eq!(matcher.estimated_attempts(), BigInt::from(256));
+    }
+
+    #[test]
+    fn test_lucky_patterns() {
+        let matcher = PubkeyMatcher::from_pattern(Pattern::All(
+            lucky_patterns(Some("8,88,888"), Some("4")).unwrap(),
+        ));
+        assert!(matcher.matches_address(1238885));
+        // 888 is lucky, but 4 is not
+        assert!(!matcher.matches_address(1238884));
+        assert!(!matcher.matches_address(1234567));
+        assert_eq!(
+            matcher.describe(),
+            "(contains 8 or contains 88 or contains 888) and without 4"
+        );
+
+        // 44 is dropped as it contains 4
+        assert_eq!(
+            lucky_patterns(Some("44,8"), Some("4")).unwrap()[0],
+            Pattern::Any(vec![Pattern::Condition(Condition::Contains(
+                "8".to_string()
+            ))])
+        );
+        assert!(lucky_patterns(Some("44"), Some("4")).is_err());
+        assert!(lucky_patterns(Some("8,"), None).is_err());
+        assert!(lucky_patterns(Some("8;4"), None).is_err());
+        assert_eq!(lucky_patterns(None, None).unwrap(), Vec::new());
+
+        // ~19.4 positions without a 4: 0.9^19.4 = ~0.13
+        let unlucky: PubkeyMatcher = "excludes:4".parse().unwrap();
+        assert_eq!(unlucky.estimated_attempts(), BigInt::from(7));
+        let rare: PubkeyMatcher = "contains:888 & excludes:4".parse().unwrap();
+        assert!(rare.estimated_attempts() > BigInt::from(7 * 55));
+    }
+
+    #[test]
+    fn test_hash_leading_zeros() {
+        // SHA-256 of 32 zero bytes is 66687aad..., 0x66 has one leading zero bit
+        let zero_key = [0u8; 32];
+        assert!("hashzeros:1"
+            .parse::<PubkeyMatcher>()
+            .unwrap()
+            .matches(&zero_key));
+        assert!(!"hashzeros:2"
+            .parse::<PubkeyMatcher>()
+            .unwrap()
+            .matches(&zero_key));
+        // SHA-256 of 0x15ed followed by 30 zero bytes is 000592f1..., 13 leading zero bits
+        let mut key = [0u8; 32];
+        key[0] = 0x15;
+        key[1] = 0xed;
+        assert!("hashzeros:13"
+            .parse::<PubkeyMatcher>()
+            .unwrap()
+            .matches(&key));
+        assert!(!"hashzeros:14"
+            .parse::<PubkeyMatcher>()
+            .unwrap()
+            .matches(&key));
+
+        // the first digest byte is the lowest byte of the address
+        let condition = Condition::HashLeadingZeros(20);
+        let digest_start = [0x00, 0x00, 0x0f, 0xff, 0xff, 0xff, 0xff, 0xff];
+        assert!(condition.matches(u64::from_le_bytes(digest_start), ""));
+        let digest_start = [0x00, 0x00, 0x1f, 0x00, 0x00, 0x00, 0x00, 0x00];
+        assert!(!condition.matches(u64::from_le_bytes(digest_start), ""));
+        assert!(Condition::HashLeadingZeros(64).matches(0, ""));
+
+        let matcher: PubkeyMatcher = "hashzeros:20".parse().unwrap();
+        assert_eq!(matcher.estimated_attempts(), BigInt::from(1 << 20));
+        assert_eq!(matcher.describe(), "20 leading zero bits of the hash");
+        assert!("hashzeros:0".parse::<PubkeyMatcher>().is_err());
+        assert!("hashzeros:65".parse::<PubkeyMatcher>().is_err());
+        assert!("hashzeros:99999999999".parse::<PubkeyMatcher>().is_err());
+    }
+
+    #[test]
+    fn test_in_base() {
+        assert_eq!(render_in_base(0, 16), "0");
+        assert_eq!(render_in_base(255, 16), "ff");
+        assert_eq!(render_in_base(5, 2), "101");
+        assert_eq!(render_in_base(u64::MAX, 36), "3w5e11264sgsf");
+        assert_eq!(max_length_in_base(2), 64);
+
+        let prefix = PubkeyMatcher::parse_in_base("prefix:FF & len<=8", 16).unwrap();
+        assert!(prefix.matches_address(0xff12));
+        assert!(!prefix.matches_address(0xfe12));
+        // the length stays decimal
+        assert!(!prefix.matches_address(0xff_1234_5678));
+        assert_eq!(prefix.describe(), "prefix ff in base 16 and max length 8");
+
+        let suffix = PubkeyMatcher::parse_in_base("suffix:ff", 16).unwrap();
+        assert!(suffix.matches_address(0x12ff));
+        assert_eq!(suffix.estimated_attempts(), BigInt::from(256));
+        let contains = PubkeyMatcher::parse_in_base("contains:zz", 36).unwrap();
+        assert!(contains.matches_address(36 * 36 * 36 - 1));
+        assert!(!contains.matches_address(36 * 36 - 2));
+
+        // every address except 0 starts with 1 in base 2
+        let binary = PubkeyMatcher::parse_in_base("prefix:1", 2).unwrap();
+        assert_eq!(
+            binary.pattern(),
+            &Pattern::Condition(Condition::InBase(
+                2,
+                Box::new(Condition::Prefix("1".to_string()))
+            ))
+        );
+        assert_eq!(
+            Condition::InBase(2, Box::new(Condition::Prefix("1".to_string()))).match_count(),
+            Some(u64::MAX as u128)
+        );
+
+        assert!(PubkeyMatcher::parse_in_base("prefix:g", 16).is_err());
+        assert!(PubkeyMatcher::parse_in_base("suffix:2", 2).is_err());
+        assert!(PubkeyMatcher::parse_in_base(&format!("prefix:{}", "1".repeat(14)), 36).is_err());
+        assert_eq!(
+            PubkeyMatcher::parse_in_base("prefix:12", 10)
+                .unwrap()
+                .pattern(),
+            "prefix:12".parse::<PubkeyMatcher>().unwrap().pattern()
+        );
+    }
+
+    fn binomial_coefficient(n: u32, k: u32) -> u128 {
+        (0..k).fold(1, |c, i| c * (n - i) as u128 / (i + 1) as u128)
+    }
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(
+            parse_address("6076671634347365051L"),
+            Ok(6076671634347365051)
+        );
+        assert_eq!(parse_address("0"), Ok(0));
+        assert!(parse_address("").is_err());
+        assert!(parse_address("0123").is_err());
+        assert!(parse_address("-1").is_err());
+        assert!(parse_address("18446744073709551616").is_err());
+    }
+
+    #[test]
+    fn test_shared_digits() {
+        assert_eq!(shared_digits("123456", "123456"), 12);
+        assert_eq!(shared_digits("123456", "129956"), 4);
+        assert_eq!(shared_digits("123456", "923459"), 0);
+        assert_eq!(shared_digits("1234", "123"), 3);
+    }
+
+    #[test]
+    fn test_score_with_target() {
+        let address = 6076671634347365051;
+        let matcher = PubkeyMatcher::new(20);
+        assert_eq!(matcher.score_address(address), 6076671634347365051);
+        // all 19 digits shared as prefix and suffix
+        let matcher = PubkeyMatcher::new(20).with_target(6076671634347365051);
+        assert_eq!(matcher.score_address(address), 40 - 2 * 19);
+        let matcher = PubkeyMatcher::new(20).with_target(6076000000000000051);
+        // "6076" and "051"
+        assert_eq!(matcher.score_address(address), 40 - 7);
+    }
+
+    #[test]
+    fn test_like_conditions() {
+        assert_eq!(
+            like_conditions("6076671634347365051L", 6, 4).unwrap(),
+            vec![
+                Condition::Prefix("607667".to_string()),
+                Condition::Suffix("5051".to_string()),
+            ]
+        );
+        assert_eq!(
+            like_conditions("6076671634347365051", 0, 2).unwrap(),
+            vec![Condition::Suffix("51".to_string())]
+        );
+        assert_eq!(like_conditions("123", 0, 0).unwrap(), vec![]);
+        assert_eq!(
+            like_conditions("123", 3, 3).unwrap(),
+            vec![
+                Condition::Prefix("123".to_string()),
+                Condition::Suffix("123".to_string()),
+            ]
+        );
+
+        assert!(like_conditions("", 1, 1).is_err());
+        assert!(like_conditions("L", 1, 1).is_err());
+        assert!(like_conditions("12a4L", 1, 1).is_err());
+        assert!(like_conditions("0123", 1, 1).is_err());
+        assert!(like_conditions("18446744073709551616", 1, 1).is_err());
+        assert!(like_conditions("123", 4, 0).is_err());
+        assert!(like_conditions("123", 0, 4).is_err());
+    }
+
+    #[test]
+    fn test_luhn() {
+        assert!(luhn_valid("0"));
+        assert!(luhn_valid("18"));
+        assert!(luhn_valid("79927398713"));
+        assert!(luhn_valid("4539578763621486"));
+        assert!(!luhn_valid("1"));
+        assert!(!luhn_valid("79927398710"));
+        assert!(!luhn_valid("4539578763621487"));
+
+        let matcher: PubkeyMatcher = "luhn".parse().unwrap();
+        assert!(matcher.matches_address(79927398713));
+        assert!(!matcher.matches_address(79927398714));
+
+        let matcher: PubkeyMatcher = "len<=15 & luhn".parse().unwrap();
+        // 10 times the attempts of len<=15 alone
+        assert_eq!(matcher.estimated_attempts(), BigInt::from(184467));
+    }
+
+    #[test]
+    fn test_gpu_max_address_value() {
+        let matcher: PubkeyMatcher = "len<=12".parse().unwrap();
+        assert_eq!(matcher.gpu_max_address_value(), Some(999999999999));
+        let matcher: PubkeyMatcher = "len<=12 & suffix:7".parse().unwrap();
+        assert_eq!(matcher.gpu_max_address_value(), None);
+    }
+
+    #[test]
+    fn test_parse_patterns_file() {
+        let content = "# goals\nsuffix:777\n\n  len<=12 | prefix:42  \n";
+        let goals = parse_patterns_file(content, 10).unwrap();
+        assert_eq!(goals.len(), 2);
+        assert_eq!(goals[0].describe(), "line 2 (suffix 777)");
+        assert_eq!(goals[1].line, 4);
+        assert!(goals[0].matcher.matches_address(1_234_567_890_123_777));
+        assert!(!goals[1].matcher.matches_address(1_234_567_890_123_777));
+
+        assert_eq!(
+            parse_patterns_file("suffix:1\nlen<=\n", 10).err(),
+            Some("Line 2: Expected digits at position 5".to_string())
+        );
+        assert!(parse_patterns_file("# nothing\n\n", 10).is_err());
+
+        let goals = parse_patterns_file("prefix:cafe\n", 16).unwrap();
+        assert!(goals[0].matcher.matches_address(0xcafe_1234));
+        assert!(!goals[0].matcher.matches_address(0xbeef_1234));
+    }
 }
@@ -0,0 +1,226 @@
+// A recipe is a self-contained description of a found key (see --recipe): the base64
+// (standard alphabet, with padding) encoding of these 44 bytes:
+//
+//   offset  size  content
+//   0       1     format version, currently 1
+//   1       1     key type: 0 = Lisk passphrase, 1 = Ed25519 key pair seed
+//   2       1     address scheme: 0 = first 8 bytes of SHA-256(public key), little endian
+//   3       1     number of passphrase words (12), 0 for key pairs
+//   4       32    key material as in "--simple-output"
+//   36      8     address, big endian
+//
+// Decoders must reject unknown versions, such that the format can be extended later.
+
+use derivation::{pubkey_to_address, secret_to_pubkey, GenerateKeyType};
+
+pub const RECIPE_VERSION: u8 = 1;
+const RECIPE_LENGTH: usize = 44;
+const ADDRESS_SCHEME_SHA256: u8 = 0;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Recipe {
+    pub key_type: GenerateKeyType,
+    pub key_material: [u8; 32],
+    pub address: u64,
+}
+
+impl Recipe {
+    pub fn encode(&self) -> String {
+        let mut bytes = Vec::with_capacity(RECIPE_LENGTH);
+        bytes.extend(&[
+            RECIPE_VERSION,
+            self.key_type.code(),
+            ADDRESS_SCHEME_SHA256,
+            self.key_type.words() as u8,
+        ]);
+        bytes.extend(&self.key_material);
+        bytes.extend(&self.address.to_be_bytes());
+        base64_encode(&bytes)
+    }
+
+    pub fn decode(input: &str) -> Result<Recipe, String> {
+        let bytes = base64_decode(input.trim())?;
+        if bytes.is_empty() {
+            return Err("Recipe is empty".to_string());
+        }
+        if bytes[0] != RECIPE_VERSION {
+            return Err(format!("Unsupported recipe version {}", bytes[0]));
+        }
+        if bytes.len() != RECIPE_LENGTH {
+            return Err(format!(
+                "Expected {} bytes in a version {} recipe, got {}",
+                RECIPE_LENGTH,
+                RECIPE_VERSION,
+                bytes.len()
+            ));
+        }
+        let key_type = GenerateKeyType::from_code(bytes[1])
+            .ok_or_else(|| format!("Unknown key type {}", bytes[1]))?;
+        if bytes[3] as usize != key_type.words() {
+            return Err(format!(
+                "Unsupported word count {} for key type {}",
+                bytes[3], bytes[1]
+            ));
+        }
+        if bytes[2] != ADDRESS_SCHEME_SHA256 {
+            return Err(format!("Unknown address scheme {}", bytes[2]));
+        }
+        let mut key_material = [0u8; 32];
+        key_material.copy_from_slice(&bytes[4..36]);
+        let mut address = [0u8; 8];
+        address.copy_from_slice(&bytes[36..44]);
+        Ok(Recipe {
+            key_type,
+            key_material,
+            address: u64::from_be_bytes(address),
+        })
+    }
+
+    // Derives the address from the key material. Returns the derived address in the
+    // error case if it does not match the recorded one.
+    pub fn verify(&self) -> Result<u64, u64> {
+        let address = pubkey_to_address(&secret_to_pubkey(self.key_material, self.key_type));
+        if address == self.address {
+            Ok(address)
+        } else {
+            Err(address)
+        }
+    }
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let indices = [
+            b[0] >> 2,
+            (b[0] & 0x03) << 4 | b[1] >> 4,
+            (b[1] & 0x0f) << 2 | b[2] >> 6,
+            b[2] & 0x3f,
+        ];
+        for (i, &index) in indices.iter().enumerate() {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base64_decode(input: &str) -> Result<Vec<u8>, String> {
+    let input = input.as_bytes();
+    let mut out = Vec::with_capacity(input.len() / 4 * 3);
+    for (n, chunk) in input.chunks(4).enumerate() {
+        if chunk.len() != 4 {
+            return Err("Invalid base64: length is not a multiple of 4".to_string());
+        }
+        let last = (n + 1) * 4 == input.len();
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return Err("Invalid base64: unexpected padding".to_string());
+        }
+        let mut value: u32 = 0;
+        for &c in &chunk[..4 - padding] {
+            let index = BASE64_ALPHABET
+                .iter()
+                .position(|&a| a == c)
+                .ok_or_else(|| format!("Invalid base64 character '{}'", c as char))?;
+            value = value << 6 | index as u32;
+        }
+        value <<= 6 * padding as u32;
+        let decoded = [(value >> 16) as u8, (value >> 8) as u8, value as u8];
+        out.extend(&decoded[..3 - padding]);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    // importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        // RFC 4648 test vectors
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for &(plain, encoded) in vectors.iter() {
+            assert_eq!(base64_encode(plain.as_bytes()), encoded);
+            assert_eq!(base64_decode(encoded).unwrap(), plain.as_bytes());
+        }
+        assert!(base64_decode("Zm9").is_err());
+        assert!(base64_decode("Zm=v").is_err());
+        assert!(base64_decode("Zg==Zg==").is_err());
+        assert!(base64_decode("Zm9*").is_err());
+    }
+
+    #[test]
+    fn test_recipe_roundtrip() {
+        let mut key_material = [0u8; 32];
+        hex::decode_to_slice(
+            "456c62af90d3dfd765b7d4b56038cbe19afa5aea9cf3aa3b1e9e476c8cafbbc2",
+            &mut key_material,
+        )
+        .unwrap();
+        let recipe = Recipe {
+            key_type: GenerateKeyType::PrivateKey,
+            key_material,
+            address: 550592072897524,
+        };
+        let encoded = recipe.encode();
+        assert_eq!(encoded.len(), 60);
+        assert_eq!(Recipe::decode(&encoded), Ok(recipe));
+        assert_eq!(recipe.verify(), Ok(550592072897524));
+
+        let passphrase = Recipe {
+            key_type: GenerateKeyType::LiskPassphrase,
+            ..recipe
+        };
+        let decoded = Recipe::decode(&passphrase.encode()).unwrap();
+        assert_eq!(decoded, passphrase);
+        // same key material, different derivation
+        assert!(decoded.verify().is_err());
+    }
+
+    #[test]
+    fn test_recipe_decode_errors() {
+        let recipe = Recipe {
+            key_type: GenerateKeyType::PrivateKey,
+            key_material: [7u8; 32],
+            address: 1,
+        };
+        let mut bytes = base64_decode(&recipe.encode()).unwrap();
+
+        bytes[0] = 2;
+        assert_eq!(
+            Recipe::decode(&base64_encode(&bytes)),
+            Err("Unsupported recipe version 2".to_string())
+        );
+        bytes[0] = RECIPE_VERSION;
+        bytes[1] = 5;
+        assert!(Recipe::decode(&base64_encode(&bytes)).is_err());
+        bytes[1] = 0;
+        bytes[3] = 24;
+        assert!(Recipe::decode(&base64_encode(&bytes)).is_err());
+        bytes[3] = 12;
+        assert!(Recipe::decode(&base64_encode(&bytes)).is_ok());
+        assert!(Recipe::decode(&base64_encode(&bytes[..43])).is_err());
+        assert!(Recipe::decode("").is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use std::fs;
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use error::Error;
+
+// Where a CPU worker started (see --record and --replay)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThreadRecord {
+    // The first key tried
+    pub start: [u8; 32],
+    // Seed of the ChaCha20 generator used for re-randomizing the key
+    pub rng_seed: [u8; 32],
+}
+
+// Everything needed to repeat the key sequence of all CPU workers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub seed_increment_bits: usize,
+    pub cpu_batch: usize,
+    pub threads: Vec<ThreadRecord>,
+}
+
+// Writes the record as text. `comment` lines (e.g. the searched pattern) are prefixed with
+// "#" and ignored when loading.
+pub fn save_record(path: &Path, record: &Record, comment: &str) -> Result<(), Error> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    // The start keys are private keys
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path)?;
+    for line in comment.lines() {
+        writeln!(file, "# {}", line)?;
+    }
+    writeln!(file, "seed-increment-bits {}", record.seed_increment_bits)?;
+    writeln!(file, "cpu-batch {}", record.cpu_batch)?;
+    for thread in &record.threads {
+        writeln!(
+            file,
+            "thread {} {}",
+            hex::encode_upper(thread.start),
+            hex::encode_upper(thread.rng_seed)
+        )?;
+    }
+    file.sync_all()?;
+    Ok(())
+}
+
+fn parse_key(value: &str) -> Option<[u8; 32]> {
+    let mut key = [0u8; 32];
+    hex::decode_to_slice(value, &mut key).ok()?;
+    Some(key)
+}
+
+pub fn load_record(path: &Path) -> Result<Record, Error> {
+    let content = fs::read_to_string(path)?;
+    let invalid = |line: &str| {
+        Error::Argument(format!(
+            "Invalid line \"{}\" in record {}",
+            line,
+            path.display()
+        ))
+    };
+    let mut seed_increment_bits = None;
+    let mut cpu_batch = None;
+    let mut threads = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields.as_slice() {
+            ["seed-increment-bits", bits] => {
+                seed_increment_bits = Some(bits.parse().map_err(|_| invalid(line))?)
+            }
+            ["cpu-batch", batch] => cpu_batch = Some(batch.parse().map_err(|_| invalid(line))?),
+            ["thread", start, rng_seed] => threads.push(ThreadRecord {
+                start: parse_key(start).ok_or_else(|| invalid(line))?,
+                rng_seed: parse_key(rng_seed).ok_or_else(|| invalid(line))?,
+            }),
+            _ => return Err(invalid(line)),
+        }
+    }
+    match (seed_increment_bits, cpu_batch) {
+        (Some(seed_increment_bits), Some(cpu_batch)) if !threads.is_empty() => Ok(Record {
+            seed_increment_bits,
+            cpu_batch,
+            threads,
+        }),
+        _ => Err(Error::Argument(format!(
+            "Incomplete record {}",
+            path.display()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // importing names from outer (for mod tests) scope.
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn test_record_roundtrip() {
+        let dir = env::temp_dir().join(format!("lisk-vanity-record-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("record");
+
+        let record = Record {
+            seed_increment_bits: 64,
+            cpu_batch: 4,
+            threads: vec![
+                ThreadRecord {
+                    start: [1u8; 32],
+                    rng_seed: [2u8; 32],
+                },
+                ThreadRecord {
+                    start: [0xabu8; 32],
+                    rng_seed: [0xcdu8; 32],
+                },
+            ],
+        };
+        save_record(&path, &record, "Searching for: max length 12").unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("# Searching for: max length 12\n"));
+        assert_eq!(load_record(&path).unwrap(), record);
+
+        fs::write(&path, "seed-increment-bits 256\ncpu-batch 1\n").unwrap();
+        assert!(load_record(&path).is_err());
+        fs::write(
+            &path,
+            "seed-increment-bits 256\ncpu-batch 1\nthread 0101 0202\n",
+        )
+        .unwrap();
+        assert!(load_record(&path).is_err());
+        fs::write(&path, "stride 1\n").unwrap();
+        assert!(load_record(&path).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,346 @@
+// A small regular expression engine for --regex, which only has to match short ASCII strings
+// like "1234L". Supports literals, ".", classes like "[0-4]" or "[^7]", "\d", the anchors "^"
+// and "$", groups with "|" and the quantifiers "*", "+", "?", "{n}", "{n,}" and "{n,m}".
+// Unless anchored, a match may start and end anywhere, like `grep`.
+
+use std::fmt;
+
+// Upper bound of "{n,m}", to keep the pattern small
+const MAX_REPEAT: usize = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Literal(u8),
+    Any,
+    // Inclusive byte ranges, negated for "[^...]"
+    Class(Vec<(u8, u8)>, bool),
+    Start,
+    End,
+    Concat(Vec<Node>),
+    Alternation(Vec<Node>),
+    Repeat(Box<Node>, usize, Option<usize>),
+}
+
+impl Node {
+    // Backtracking: calls `next` with every position a match of this node starting at `pos`
+    // can end at, until `next` returns true
+    fn matches(&self, text: &[u8], pos: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+        match self {
+            Node::Literal(byte) => pos < text.len() && text[pos] == *byte && next(pos + 1),
+            Node::Any => pos < text.len() && next(pos + 1),
+            Node::Class(ranges, negated) => {
+                pos < text.len()
+                    && ranges
+                        .iter()
+                        .any(|&(low, high)| (low..=high).contains(&text[pos]))
+                        != *negated
+                    && next(pos + 1)
+            }
+            Node::Start => pos == 0 && next(pos),
+            Node::End => pos == text.len() && next(pos),
+            Node::Concat(nodes) => match_sequence(nodes, text, pos, next),
+            Node::Alternation(options) => options
+                .iter()
+                .any(|option| option.matches(text, pos, &mut *next)),
+            Node::Repeat(node, min, max) => match_repeat(node, *min, *max, 0, text, pos, next),
+        }
+    }
+}
+
+fn match_sequence(
+    nodes: &[Node],
+    text: &[u8],
+    pos: usize,
+    next: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    match nodes.split_first() {
+        None => next(pos),
+        Some((first, rest)) => {
+            first.matches(text, pos, &mut |end| match_sequence(rest, text, end, next))
+        }
+    }
+}
+
+// Greedy: tries one more repetition before stopping
+fn match_repeat(
+    node: &Node,
+    min: usize,
+    max: Option<usize>,
+    count: usize,
+    text: &[u8],
+    pos: usize,
+    next: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    if !matches!(max, Some(max) if count >= max)
+        && node.matches(text, pos, &mut |end| {
+            // An empty repetition can be repeated as often as needed without moving
+            if end == pos {
+                next(end)
+            } else {
+                match_repeat(node, min, max, count + 1, text, end, next)
+            }
+        })
+    {
+        return true;
+    }
+    count >= min && next(pos)
+}
+
+#[derive(Clone)]
+pub struct Regex {
+    source: String,
+    node: Node,
+}
+
+impl fmt::Debug for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Regex({:?})", self.source)
+    }
+}
+
+impl PartialEq for Regex {
+    fn eq(&self, other: &Regex) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Regex {
+    pub fn new(source: &str) -> Result<Regex, String> {
+        let mut parser = Parser {
+            input: source.as_bytes(),
+            pos: 0,
+        };
+        let node = parser.alternation()?;
+        if parser.pos < parser.input.len() {
+            return Err(format!("Unmatched \")\" at position {}", parser.pos));
+        }
+        Ok(Regex {
+            source: source.to_string(),
+            node,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn is_match(&self, text: &[u8]) -> bool {
+        (0..=text.len()).any(|start| self.node.matches(text, start, &mut |_| true))
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).cloned()
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut options = vec![self.concat()?];
+        while self.eat(b'|') {
+            options.push(self.concat()?);
+        }
+        Ok(if options.len() == 1 {
+            options.pop().unwrap()
+        } else {
+            Node::Alternation(options)
+        })
+    }
+
+    fn concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(byte) = self.peek() {
+            if byte == b'|' || byte == b')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn quantified(&mut self, mut node: Node) -> Result<Node, String> {
+        loop {
+            let start = self.pos;
+            let (min, max) = if self.eat(b'*') {
+                (0, None)
+            } else if self.eat(b'+') {
+                (1, None)
+            } else if self.eat(b'?') {
+                (0, Some(1))
+            } else if self.eat(b'{') {
+                self.bounds()?
+            } else {
+                return Ok(node);
+            };
+            if let Node::Start | Node::End = node {
+                return Err(format!("Nothing to repeat at position {}", start));
+            }
+            node = Node::Repeat(Box::new(node), min, max);
+        }
+    }
+
+    // The rest of "{n}", "{n,}" or "{n,m}"
+    fn bounds(&mut self) -> Result<(usize, Option<usize>), String> {
+        let start = self.pos - 1;
+        let min = self.number(start)?;
+        let max = if self.eat(b',') {
+            if self.peek() == Some(b'}') {
+                None
+            } else {
+                Some(self.number(start)?)
+            }
+        } else {
+            Some(min)
+        };
+        if !self.eat(b'}') {
+            return Err(format!("Invalid repetition at position {}", start));
+        }
+        if max.map_or(min > MAX_REPEAT, |max| max < min || max > MAX_REPEAT) {
+            return Err(format!(
+                "Invalid repetition at position {}, the bounds must be ordered and at most {}",
+                start, MAX_REPEAT
+            ));
+        }
+        Ok((min, max))
+    }
+
+    fn number(&mut self, start: usize) -> Result<usize, String> {
+        let digits_start = self.pos;
+        while matches!(self.peek(), Some(byte) if byte.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        String::from_utf8_lossy(&self.input[digits_start..self.pos])
+            .parse()
+            .map_err(|_| format!("Invalid repetition at position {}", start))
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let start = self.pos;
+        let byte = self.peek().unwrap();
+        self.pos += 1;
+        match byte {
+            b'(' => {
+                let node = self.alternation()?;
+                if !self.eat(b')') {
+                    return Err(format!("Unmatched \"(\" at position {}", start));
+                }
+                Ok(node)
+            }
+            b'[' => self.class(start),
+            b'.' => Ok(Node::Any),
+            b'^' => Ok(Node::Start),
+            b'$' => Ok(Node::End),
+            b'\\' => self.escape(start),
+            b'*' | b'+' | b'?' | b'{' => Err(format!("Nothing to repeat at position {}", start)),
+            _ => Ok(Node::Literal(byte)),
+        }
+    }
+
+    fn escape(&mut self, start: usize) -> Result<Node, String> {
+        let byte = self
+            .peek()
+            .ok_or_else(|| format!("Trailing \"\\\" at position {}", start))?;
+        self.pos += 1;
+        Ok(match byte {
+            b'd' => Node::Class(vec![(b'0', b'9')], false),
+            b'D' => Node::Class(vec![(b'0', b'9')], true),
+            _ if byte.is_ascii_alphanumeric() => {
+                return Err(format!(
+                    "Unsupported escape \"\\{}\" at position {}",
+                    byte as char, start
+                ))
+            }
+            _ => Node::Literal(byte),
+        })
+    }
+
+    // The rest of "[...]" or "[^...]"
+    fn class(&mut self, start: usize) -> Result<Node, String> {
+        let negated = self.eat(b'^');
+        let mut ranges = Vec::new();
+        loop {
+            let low = match self.peek() {
+                None => return Err(format!("Unmatched \"[\" at position {}", start)),
+                Some(b']') if !ranges.is_empty() => {
+                    self.pos += 1;
+                    return Ok(Node::Class(ranges, negated));
+                }
+                Some(byte) => byte,
+            };
+            self.pos += 1;
+            let high = if self.peek() == Some(b'-') && self.input.get(self.pos + 1) != Some(&b']') {
+                self.pos += 1;
+                let high = self
+                    .peek()
+                    .ok_or_else(|| format!("Unmatched \"[\" at position {}", start))?;
+                self.pos += 1;
+                if high < low {
+                    return Err(format!("Invalid range at position {}", self.pos - 3));
+                }
+                high
+            } else {
+                low
+            };
+            ranges.push((low, high));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn is_match(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text.as_bytes())
+    }
+
+    #[test]
+    fn test_is_match() {
+        assert!(is_match("777", "1237774L"));
+        assert!(!is_match("777", "1237747L"));
+        assert!(is_match("^12", "12345L"));
+        assert!(!is_match("^12", "312345L"));
+        assert!(is_match("00L$", "1200L"));
+        assert!(is_match("^1\\d{3}L$", "1234L"));
+        assert!(!is_match("^1\\d{3}L$", "12345L"));
+        assert!(is_match("^[1-3]+[^0-9]$", "3213L"));
+        assert!(!is_match("^[1-3]+[^0-9]$", "3214L"));
+        assert!(is_match("^(12|34)*L$", "123412L"));
+        assert!(!is_match("^(12|34)*L$", "1243L"));
+        assert!(is_match("^(12|34)*L$", "L"));
+        assert!(is_match("^9?8{2,}7{1,2}L", "8887L"));
+        assert!(!is_match("^9?8{2,}7{1,2}L", "987L"));
+        assert!(is_match("^.{4}L$", "1234L"));
+        // backtracking into the greedy repetition
+        assert!(is_match("^\\d*4L$", "1234L"));
+        // repeated empty matches terminate
+        assert!(is_match("^(1*)*L$", "111L"));
+        assert!(is_match("[-]", "-"));
+        assert!(is_match("\\.", "."));
+        assert!(!is_match("\\.", "1"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        for pattern in &[
+            "(12", "12)", "[12", "[]", "*1", "1{2", "1{3,2}", "1{101}", "\\", "\\w", "[9-1]", "^*",
+        ] {
+            assert!(Regex::new(pattern).is_err(), "{}", pattern);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use derivation::{pubkey_to_address, secret_to_pubkey, GenerateKeyType};
+
+// Parses a line in the "--simple-output" format "[key] [address]". The address
+// may be given with or without the "L" suffix. Further columns, like the public key of
+// --show-pubkey, the URI of --uri or the --patterns-file lines, are ignored.
+pub fn parse_simple_output_line(line: &str) -> Result<([u8; 32], u64), String> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.len() < 2 {
+        return Err(format!(
+            "Expected \"[key] [address]\", got \"{}\"",
+            line.trim()
+        ));
+    }
+
+    let mut key_material = [0u8; 32];
+    hex::decode_to_slice(parts[0], &mut key_material)
+        .map_err(|_| format!("Invalid key \"{}\": expected 64 hex characters", parts[0]))?;
+
+    let address_digits = parts[1].trim_end_matches('L');
+    let address = address_digits
+        .parse()
+        .map_err(|_| format!("Invalid address \"{}\"", parts[1]))?;
+
+    Ok((key_material, address))
+}
+
+// Derives the address for the given key material. Returns the derived address
+// in the error case if it does not match the expected one.
+pub fn reproduce(
+    key_material: [u8; 32],
+    expected_address: u64,
+    generate_key_type: GenerateKeyType,
+) -> Result<(), u64> {
+    let address = pubkey_to_address(&secret_to_pubkey(key_material, generate_key_type));
+    if address == expected_address {
+        Ok(())
+    } else {
+        Err(address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_parse_simple_output_line() {
+        let (key, address) = parse_simple_output_line(
+            "456C62AF90D3DFD765B7D4B56038CBE19AFA5AEA9CF3AA3B1E9E476C8CAFBBC2 550592072897524",
+        )
+        .unwrap();
+        assert_eq!(key[0], 0x45);
+        assert_eq!(key[31], 0xc2);
+        assert_eq!(address, 550592072897524);
+
+        let (_, address) = parse_simple_output_line(
+            "456C62AF90D3DFD765B7D4B56038CBE19AFA5AEA9CF3AA3B1E9E476C8CAFBBC2 550592072897524L\n",
+        )
+        .unwrap();
+        assert_eq!(address, 550592072897524);
+
+        assert!(parse_simple_output_line("").is_err());
+        assert!(parse_simple_output_line("456C62AF 550592072897524").is_err());
+        assert!(parse_simple_output_line(
+            "456C62AF90D3DFD765B7D4B56038CBE19AFA5AEA9CF3AA3B1E9E476C8CAFBBC2 55059207289752xL"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_extended_simple_output_line() {
+        let key = "456C62AF90D3DFD765B7D4B56038CBE19AFA5AEA9CF3AA3B1E9E476C8CAFBBC2";
+        let pubkey = "B9ED79C32B2EA7EF9B2E2D2F5DEF7A7A1CE56A9E8C7E3EF8A9D7E9AB5F1B6B11";
+        for line in &[
+            // --show-pubkey
+            format!("{} 550592072897524 {}", key, pubkey),
+            // --uri
+            format!("{} 550592072897524 lisk:550592072897524L", key),
+            // --patterns-file
+            format!("{} 550592072897524 2,5", key),
+            format!("{} 550592072897524 {} lisk:550592072897524L 2", key, pubkey),
+        ] {
+            let (parsed_key, address) = parse_simple_output_line(line).unwrap();
+            assert_eq!(hex::encode_upper(parsed_key), key);
+            assert_eq!(address, 550592072897524);
+        }
+    }
+
+    #[test]
+    fn test_reproduce() {
+        let (key, address) = parse_simple_output_line(
+            "456C62AF90D3DFD765B7D4B56038CBE19AFA5AEA9CF3AA3B1E9E476C8CAFBBC2 550592072897524",
+        )
+        .unwrap();
+        assert_eq!(reproduce(key, address, GenerateKeyType::PrivateKey), Ok(()));
+        assert!(reproduce(key, address + 1, GenerateKeyType::PrivateKey).is_err());
+        assert!(reproduce(key, address, GenerateKeyType::LiskPassphrase).is_err());
+    }
+}
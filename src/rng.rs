@@ -0,0 +1,79 @@
+use std::str::FromStr;
+
+use rand::{ChaChaRng, OsRng, Rng, SeedableRng};
+
+use error::Error;
+
+// The generator used for the random keys of each worker
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RngSource {
+    // The operating system's generator
+    Os,
+    // ChaCha20, seeded from the operating system's generator
+    ChaCha20,
+}
+
+impl FromStr for RngSource {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<RngSource, String> {
+        match input {
+            "os" => Ok(RngSource::Os),
+            "chacha20" => Ok(RngSource::ChaCha20),
+            _ => Err(format!("Unknown RNG \"{}\"", input)),
+        }
+    }
+}
+
+pub fn new_rng(source: RngSource) -> Result<Box<dyn Rng + Send>, Error> {
+    let mut os_rng = OsRng::new()?;
+    Ok(match source {
+        RngSource::Os => Box::new(os_rng),
+        RngSource::ChaCha20 => Box::new(os_rng.gen::<ChaChaRng>()),
+    })
+}
+
+// A ChaCha20 generator that always produces the same sequence for `seed` (see --replay)
+pub fn seeded_rng(seed: &[u8; 32]) -> Box<dyn Rng + Send> {
+    let mut words = [0u32; 8];
+    for (word, bytes) in words.iter_mut().zip(seed.chunks(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    Box::new(ChaChaRng::from_seed(&words[..]))
+}
+
+#[cfg(test)]
+mod tests {
+    // importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_rng_source_from_str() {
+        assert_eq!("os".parse(), Ok(RngSource::Os));
+        assert_eq!("chacha20".parse(), Ok(RngSource::ChaCha20));
+        assert!("hc128".parse::<RngSource>().is_err());
+        assert!("".parse::<RngSource>().is_err());
+    }
+
+    #[test]
+    fn test_new_rng_is_seeded() {
+        for &source in &[RngSource::Os, RngSource::ChaCha20] {
+            let mut a = [0u8; 32];
+            let mut b = [0u8; 32];
+            new_rng(source).unwrap().fill_bytes(&mut a);
+            new_rng(source).unwrap().fill_bytes(&mut b);
+            assert_ne!(a, b);
+        }
+    }
+
+    #[test]
+    fn test_seeded_rng() {
+        let mut a = [0u8; 64];
+        let mut b = [0u8; 64];
+        seeded_rng(&[7u8; 32]).fill_bytes(&mut a);
+        seeded_rng(&[7u8; 32]).fill_bytes(&mut b);
+        assert_eq!(a[..], b[..]);
+        seeded_rng(&[8u8; 32]).fill_bytes(&mut b);
+        assert_ne!(a[..], b[..]);
+    }
+}
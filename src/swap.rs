@@ -0,0 +1,38 @@
+use std::fs;
+
+// /proc/swaps has a header line followed by one line per active swap device or file
+fn swaps_listed(proc_swaps: &str) -> bool {
+    proc_swaps
+        .lines()
+        .skip(1)
+        .any(|line| !line.trim().is_empty())
+}
+
+// Whether swap is active, such that secrets in memory may be written to disk. None where
+// this cannot be detected, i.e. outside of Linux.
+pub fn swap_active() -> Option<bool> {
+    if cfg!(target_os = "linux") {
+        fs::read_to_string("/proc/swaps")
+            .ok()
+            .map(|content| swaps_listed(&content))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_swaps_listed() {
+        let header = "Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority\n";
+        assert!(!swaps_listed(header));
+        assert!(!swaps_listed(""));
+        assert!(swaps_listed(&format!(
+            "{}/swapfile                               file\t\t2097148\t\t0\t\t-2\n",
+            header
+        )));
+    }
+}
@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+#[cfg(unix)]
+use std::mem;
+
+#[cfg(unix)]
+fn timeval_to_duration(time: libc::timeval) -> Duration {
+    Duration::new(time.tv_sec as u64, time.tv_usec as u32 * 1000)
+}
+
+// CPU time consumed by all threads of this process (user and system time), if available
+#[cfg(unix)]
+pub fn process_cpu_time() -> Option<Duration> {
+    let mut usage: libc::rusage = unsafe { mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return None;
+    }
+    Some(timeval_to_duration(usage.ru_utime) + timeval_to_duration(usage.ru_stime))
+}
+
+#[cfg(not(unix))]
+pub fn process_cpu_time() -> Option<Duration> {
+    None
+}
+
+// CPU time per thread and wall-clock second. 1.0 means all threads were busy all the time.
+pub fn parallel_efficiency(cpu_time: Duration, wall_time: Duration, threads: usize) -> Option<f64> {
+    let available = wall_time.as_secs_f64() * threads as f64;
+    if available > 0.0 {
+        Some(cpu_time.as_secs_f64() / available)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_parallel_efficiency() {
+        let second = Duration::from_secs(1);
+        assert_eq!(parallel_efficiency(second * 4, second * 2, 4), Some(0.5));
+        assert_eq!(parallel_efficiency(second * 8, second * 2, 4), Some(1.0));
+        assert_eq!(parallel_efficiency(second, Duration::from_secs(0), 4), None);
+        assert_eq!(parallel_efficiency(second, second, 0), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_process_cpu_time() {
+        let before = process_cpu_time().unwrap();
+        let mut x = 0u64;
+        for i in 0..10_000_000u64 {
+            x = x.wrapping_mul(31).wrapping_add(i);
+        }
+        assert!(x != 1);
+        assert!(process_cpu_time().unwrap() >= before);
+    }
+}
@@ -0,0 +1,93 @@
+// Enumerates 256 bit keys, interpreted as big endian numbers, from `start` in steps of
+// `stride`, wrapping around at 2^256. The iterator is endless; use `take` to limit it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeySpaceWalker {
+    // The next key returned
+    pub start: [u8; 32],
+    pub stride: [u8; 32],
+}
+
+impl KeySpaceWalker {
+    // Walks all keys from `start` in steps of 1
+    pub fn new(start: [u8; 32]) -> KeySpaceWalker {
+        let mut stride = [0u8; 32];
+        stride[31] = 1;
+        KeySpaceWalker { start, stride }
+    }
+}
+
+impl Iterator for KeySpaceWalker {
+    type Item = [u8; 32];
+
+    fn next(&mut self) -> Option<[u8; 32]> {
+        let key = self.start;
+        let mut carry = 0u16;
+        for (byte, stride) in self.start.iter_mut().zip(self.stride.iter()).rev() {
+            let sum = *byte as u16 + *stride as u16 + carry;
+            *byte = sum as u8;
+            carry = sum >> 8;
+        }
+        Some(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn key(last_bytes: &[u8]) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        key[32 - last_bytes.len()..].copy_from_slice(last_bytes);
+        key
+    }
+
+    #[test]
+    fn test_walker_steps() {
+        let keys: Vec<[u8; 32]> = KeySpaceWalker::new(key(&[0xfe])).take(3).collect();
+        assert_eq!(keys, vec![key(&[0xfe]), key(&[0xff]), key(&[1, 0x00])]);
+    }
+
+    #[test]
+    fn test_walker_stride() {
+        let walker = KeySpaceWalker {
+            start: key(&[0x10]),
+            stride: key(&[1, 0x00]),
+        };
+        let keys: Vec<[u8; 32]> = walker.take(3).collect();
+        assert_eq!(keys, vec![key(&[0x10]), key(&[1, 0x10]), key(&[2, 0x10])]);
+
+        // carry across several bytes
+        let mut walker = KeySpaceWalker {
+            start: key(&[0x00, 0xff, 0xf0]),
+            stride: key(&[0x20]),
+        };
+        assert_eq!(walker.nth(1), Some(key(&[0x01, 0x00, 0x10])));
+    }
+
+    #[test]
+    fn test_walker_wraparound() {
+        let mut walker = KeySpaceWalker::new([0xff; 32]);
+        assert_eq!(walker.next(), Some([0xff; 32]));
+        assert_eq!(walker.next(), Some([0u8; 32]));
+
+        let mut walker = KeySpaceWalker {
+            start: [0xff; 32],
+            stride: [0xff; 32],
+        };
+        walker.next();
+        // 2^256 - 1 + 2^256 - 1 = 2^256 - 2 (mod 2^256)
+        let mut expected = [0xff; 32];
+        expected[31] = 0xfe;
+        assert_eq!(walker.next(), Some(expected));
+    }
+
+    #[test]
+    fn test_walker_zero_stride() {
+        let walker = KeySpaceWalker {
+            start: key(&[7]),
+            stride: [0u8; 32],
+        };
+        assert!(walker.take(3).all(|k| k == key(&[7])));
+    }
+}
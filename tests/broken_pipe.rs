@@ -0,0 +1,35 @@
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+
+// Closing stdout, like `| head -n 1` does, stops an unlimited search cleanly instead of
+// panicking on the next match
+#[test]
+fn test_closed_stdout_stops_search() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lisk-vanity"))
+        .args([
+            "--pattern",
+            "len<=20",
+            "--cpu-threads",
+            "2",
+            "--limit",
+            "0",
+            "--simple-output",
+            "--no-progress",
+            "--i-understand-swap-risk",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut first_line = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut first_line)
+        .unwrap();
+    assert!(!first_line.is_empty());
+
+    let status = child.wait().unwrap();
+    let mut stderr = String::new();
+    child.stderr.unwrap().read_to_string(&mut stderr).unwrap();
+    assert!(status.success(), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
@@ -0,0 +1,44 @@
+#![cfg(unix)]
+extern crate libc;
+
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+
+// Ctrl-C is the usual end of a --limit 0 harvest, so it still prints the difficulty tiers
+#[test]
+fn test_interrupt_prints_difficulty_tiers() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lisk-vanity"))
+        .args([
+            "--pattern",
+            "len<=20",
+            "--cpu-threads",
+            "2",
+            "--limit",
+            "0",
+            "--group-by-difficulty",
+            "--simple-output",
+            "--no-progress",
+            "--i-understand-swap-risk",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut first_line = String::new();
+    stdout.read_line(&mut first_line).unwrap();
+    assert!(!first_line.is_empty());
+
+    assert_eq!(
+        unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) },
+        0
+    );
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    let status = child.wait().unwrap();
+    let mut stderr = String::new();
+    child.stderr.unwrap().read_to_string(&mut stderr).unwrap();
+    assert!(status.success(), "{}", stderr);
+    assert!(stderr.contains("Interrupted after"), "{}", stderr);
+    assert!(stderr.contains("Matches by difficulty:"), "{}", stderr);
+}
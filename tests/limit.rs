@@ -0,0 +1,50 @@
+use std::process::Command;
+
+// The output thread is the only consumer of the matches, so --limit must be exact even if many
+// workers find matches at the same time. Every address matches "len<=20", so each thread finds
+// a match with every key.
+#[test]
+fn test_limit_is_exact_under_concurrency() {
+    for _ in 0..5 {
+        let output = Command::new(env!("CARGO_BIN_EXE_lisk-vanity"))
+            .args([
+                "--pattern",
+                "len<=20",
+                "--cpu-threads",
+                "16",
+                "--limit",
+                "3",
+                "--simple-output",
+                "--no-progress",
+                "--i-understand-swap-risk",
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(stdout.lines().count(), 3, "{}", stdout);
+    }
+}
+
+// Without a shorter match in the grace period, the best match was already output and is not
+// repeated in machine readable output
+#[test]
+fn test_grace_does_not_repeat_simple_output() {
+    let output = Command::new(env!("CARGO_BIN_EXE_lisk-vanity"))
+        .args([
+            "--exact-length",
+            "20",
+            "--limit",
+            "1",
+            "--grace",
+            "1",
+            "--simple-output",
+            "--no-progress",
+            "--i-understand-swap-risk",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1, "{}", stdout);
+}
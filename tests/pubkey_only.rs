@@ -0,0 +1,66 @@
+use std::process::Command;
+
+fn run_pubkey_only(extra_args: &[&str]) -> (String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_lisk-vanity"))
+        .args([
+            "--pattern",
+            "len<=20",
+            "--cpu-threads",
+            "2",
+            "--limit",
+            "3",
+            "--output-pubkey-only",
+        ])
+        .args(extra_args)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+    (stdout, stderr)
+}
+
+// Runs of at least 32 hex characters, i.e. anything that could be key material
+fn hex_runs(text: &str) -> Vec<&str> {
+    text.split(|c: char| !c.is_ascii_hexdigit())
+        .filter(|run| run.len() >= 32)
+        .collect()
+}
+
+// --output-pubkey-only must never reveal a secret, neither on stdout nor on stderr
+#[test]
+fn test_pubkey_only_outputs_no_secret() {
+    let (stdout, stderr) = run_pubkey_only(&["--generate-keypair"]);
+    let public_keys: Vec<&str> = stdout
+        .lines()
+        .map(|line| line.split(',').next().unwrap())
+        .collect();
+    assert_eq!(public_keys.len(), 3, "{}", stdout);
+    for run in hex_runs(&stdout).into_iter().chain(hex_runs(&stderr)) {
+        assert!(public_keys.contains(&run), "secret in output: {}", run);
+    }
+
+    // Passphrases are words
+    let (stdout, stderr) = run_pubkey_only(&[]);
+    for line in stdout.lines().chain(stderr.lines()) {
+        let words = line
+            .split_whitespace()
+            .filter(|word| word.chars().all(|c| c.is_ascii_lowercase()))
+            .count();
+        assert!(words < 12, "passphrase in output: {}", line);
+    }
+    assert!(!stderr.contains("Private Key"), "{}", stderr);
+}
+
+// --debug-derivation prints the private key, so it is rejected instead of ignored
+#[test]
+fn test_pubkey_only_rejects_debug_derivation() {
+    let output = Command::new(env!("CARGO_BIN_EXE_lisk-vanity"))
+        .args(["--output-pubkey-only", "--debug-derivation"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(hex_runs(&stderr).is_empty(), "{}", stderr);
+}
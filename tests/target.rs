@@ -0,0 +1,25 @@
+use std::process::Command;
+
+// The closest account is the result of a --target search, so exhausting the budget without
+// finding the target itself is a success
+#[test]
+fn test_target_reports_closest_account() {
+    let output = Command::new(env!("CARGO_BIN_EXE_lisk-vanity"))
+        .args([
+            "--target",
+            "1234567890123456789L",
+            "--budget",
+            "1000",
+            "--cpu-threads",
+            "1",
+            "--no-progress",
+            "--i-understand-swap-risk",
+        ])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stdout.contains("Closest account:"), "{}", stdout);
+    assert!(!stderr.contains("Error"), "{}", stderr);
+}